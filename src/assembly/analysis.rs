@@ -0,0 +1,282 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use super::{Instruction, Opcode, Operand, Program, Registers};
+
+/// How a program ends when run from the start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The instruction pointer left the program (or a `mod` by 0 halted it)
+    Terminated { acc: isize },
+    /// An instruction was about to run a second time.
+    /// `acc` is the accumulator right before that, and `cycle` lists the loop's instructions in execution order.
+    Looped {
+        acc: isize,
+        loop_start: usize,
+        cycle: Vec<usize>,
+    },
+}
+
+/// The instruction at `index` moves the instruction pointer depending on the registers,
+/// so the program can't be analysed statically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynamicFlow {
+    pub index: usize,
+}
+
+/// A `jmp`/`nop` swap that makes a program terminate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch {
+    pub index: usize,
+    /// The replacement for the instruction at `index`
+    pub instruction: Instruction,
+    /// The accumulator once the patched program terminates
+    pub acc: isize,
+}
+
+/// The instruction with `jmp` swapped for `nop` or vice versa
+fn swap(inst: Instruction) -> Option<Instruction> {
+    let opcode = match (inst.opcode, inst.args[0]) {
+        (Opcode::Jump, _) => Opcode::NoOp,
+        (Opcode::NoOp, Operand::Value(_)) => Opcode::Jump,
+        _ => return None,
+    };
+    Some(Instruction { opcode, ..inst })
+}
+
+impl Program {
+    /// The instruction that runs after the one at `index`, or `None` if the program ends there
+    fn successor(&self, index: usize, inst: &Instruction) -> Result<Option<usize>, DynamicFlow> {
+        let offset = match (inst.opcode, inst.args) {
+            (Opcode::Jump, [Operand::Value(v), _]) => v,
+            (Opcode::JumpNotZero, [Operand::Value(x), Operand::Value(y)]) if x != 0 => y,
+            (Opcode::JumpGreaterZero, [Operand::Value(x), Operand::Value(y)]) if x > 0 => y,
+            (Opcode::JumpNotZero, [Operand::Value(_), Operand::Value(_)])
+            | (Opcode::JumpGreaterZero, [Operand::Value(_), Operand::Value(_)]) => 1,
+            (Opcode::Mod, [_, Operand::Value(0)]) => return Ok(None),
+            (Opcode::Mod, [_, Operand::Value(_)]) => 1,
+            (Opcode::Jump, _)
+            | (Opcode::JumpNotZero, _)
+            | (Opcode::JumpGreaterZero, _)
+            | (Opcode::Mod, _)
+            | (Opcode::Custom(_), _) => return Err(DynamicFlow { index }),
+            _ => 1,
+        };
+        // Wrapping, like the machine
+        let next = (index as isize).wrapping_add(offset);
+        if next >= 0 && (next as usize) < self.instructions.len() {
+            Ok(Some(next as usize))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Follows the control flow from the first instruction, returning the visited instructions too
+    fn walk(&self) -> Result<(Outcome, Vec<usize>), DynamicFlow> {
+        let mut regs = Registers::default();
+        // Where each instruction appears in the path
+        let mut visited = vec![None; self.instructions.len()];
+        let mut path = vec![];
+        let mut ptr = if self.instructions.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        while let Some(idx) = ptr {
+            if let Some(pos) = visited[idx] {
+                let outcome = Outcome::Looped {
+                    acc: regs.accumulator(),
+                    loop_start: idx,
+                    cycle: path[pos..].to_vec(),
+                };
+                return Ok((outcome, path));
+            }
+            visited[idx] = Some(path.len());
+            path.push(idx);
+            let inst = &self.instructions[idx];
+            ptr = self.successor(idx, inst)?;
+            // The flow is static, so this only updates the registers
+            inst.run(&mut regs, &self.set);
+        }
+        let outcome = Outcome::Terminated {
+            acc: regs.accumulator(),
+        };
+        Ok((outcome, path))
+    }
+
+    /// Finds out whether the program terminates, without running it on the program's own state.
+    /// Every instruction runs at most once, so this takes linear time.
+    pub fn analyze(&self) -> Result<Outcome, DynamicFlow> {
+        self.walk().map(|(outcome, _)| outcome)
+    }
+
+    /// Finds the instruction that, swapped from `jmp` to `nop` (or vice versa), makes a looping program terminate.
+    /// Returns `None` if the program already terminates or no swap works.
+    pub fn find_patch(&self) -> Result<Option<Patch>, DynamicFlow> {
+        let len = self.instructions.len();
+        // Walk the control flow graph backwards from the instructions that end the program,
+        // to find every instruction that eventually terminates
+        let mut predecessors = vec![vec![]; len];
+        let mut queue = vec![];
+        for (i, inst) in self.instructions.iter().enumerate() {
+            match self.successor(i, inst)? {
+                Some(next) => predecessors[next].push(i),
+                None => queue.push(i),
+            }
+        }
+        let mut terminates = vec![false; len];
+        for &i in &queue {
+            terminates[i] = true;
+        }
+        while let Some(i) = queue.pop() {
+            for &prev in &predecessors[i] {
+                if !terminates[prev] {
+                    terminates[prev] = true;
+                    queue.push(prev);
+                }
+            }
+        }
+        let path = match self.walk()? {
+            (Outcome::Terminated { .. }, _) => return Ok(None),
+            (Outcome::Looped { .. }, path) => path,
+        };
+        // The first swap on the looping path that lands on a terminating instruction works:
+        // that instruction can't lead back to the swapped one, otherwise the path would have terminated.
+        for index in path {
+            let instruction = match swap(self.instructions[index]) {
+                Some(inst) => inst,
+                None => continue,
+            };
+            if self
+                .successor(index, &instruction)?
+                .is_none_or(|next| terminates[next])
+            {
+                let mut instructions = self.instructions.clone();
+                instructions[index] = instruction;
+                let patched = Program {
+                    instructions,
+                    ..self.clone()
+                };
+                if let Outcome::Terminated { acc } = patched.analyze()? {
+                    return Ok(Some(Patch {
+                        index,
+                        instruction,
+                        acc,
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like [`Program::find_patch`], but also works when the control flow depends on the registers,
+    /// by running every possible swap on its own machine, spread over `threads` threads.
+    /// A patched run that doesn't terminate within `max_steps` instructions counts as looping.
+    /// Returns the working swap with the lowest index.
+    pub fn find_patch_parallel(&self, threads: usize, max_steps: usize) -> Option<Patch> {
+        let candidates: Vec<(usize, Instruction)> = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &inst)| swap(inst).map(|inst| (i, inst)))
+            .collect();
+        // The lowest index that works so far, so threads can skip the swaps after it
+        let best = AtomicUsize::new(usize::MAX);
+        let threads = threads.max(1);
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    let candidates = &candidates;
+                    let best = &best;
+                    scope.spawn(move || {
+                        let mut machine = self.machine();
+                        let mut found = None;
+                        for &(index, instruction) in candidates.iter().skip(t).step_by(threads) {
+                            if index > best.load(Ordering::Relaxed) {
+                                break;
+                            }
+                            machine.reset();
+                            // Swapping `jmp` and `nop` can't make an instruction invalid
+                            machine
+                                .patch(index, instruction)
+                                .expect("valid swapped instruction");
+                            if let Some(acc) = machine.run_to_end(max_steps) {
+                                best.fetch_min(index, Ordering::Relaxed);
+                                found = Some(Patch {
+                                    index,
+                                    instruction,
+                                    acc,
+                                });
+                                break;
+                            }
+                        }
+                        found
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|h| h.join().unwrap())
+                .min_by_key(|p| p.index)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOOPING: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    #[test]
+    fn analyze_loop() {
+        let program = Program::from_input(LOOPING, 0).unwrap();
+        assert_eq!(
+            program.analyze(),
+            Ok(Outcome::Looped {
+                acc: 5,
+                loop_start: 1,
+                cycle: vec![1, 2, 6, 7, 3, 4]
+            })
+        );
+    }
+
+    #[test]
+    fn analyze_dynamic() {
+        let program = Program::from_input("set a 1\njnz a -1", 0).unwrap();
+        assert_eq!(program.analyze(), Err(DynamicFlow { index: 1 }));
+        let program = Program::from_input("set a 1\njnz 1 +2\nacc +1", 0).unwrap();
+        assert_eq!(program.analyze(), Ok(Outcome::Terminated { acc: 0 }));
+    }
+
+    #[test]
+    fn patch() {
+        let program = Program::from_input(LOOPING, 0).unwrap();
+        let patch = program.find_patch().unwrap().unwrap();
+        assert_eq!((patch.index, patch.instruction.opcode), (7, Opcode::NoOp));
+        assert_eq!(patch.acc, 8);
+        assert_eq!(program.find_patch_parallel(4, 1000), Some(patch));
+        let program = Program::from_input("acc +1", 0).unwrap();
+        assert_eq!(program.find_patch(), Ok(None));
+    }
+
+    #[test]
+    fn patch_dynamic() {
+        // Counts a down from 3, unless it gets stuck at index 2 first
+        let program =
+            Program::from_input("set a 3\nnop +2\njmp +0\nsub a 1\njnz a -1\nacc a", 0).unwrap();
+        assert_eq!(program.find_patch(), Err(DynamicFlow { index: 4 }));
+        let patch = program.find_patch_parallel(2, 1000).unwrap();
+        assert_eq!((patch.index, patch.acc), (1, 0));
+    }
+}
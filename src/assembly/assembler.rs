@@ -0,0 +1,281 @@
+//! Turns source code into programs and back.
+//!
+//! On top of the plain `opcode args...` lines, the source can have:
+//! - comments, from `#` or `;` to the end of the line
+//! - labels, written as `name:` either on their own line or before an instruction
+//! - label names as arguments, which assemble to the offset from the current instruction to the label,
+//!   so `jmp loop_start` works like any relative jump
+//!
+//! Single letters and `acc` are register names, so labels need at least two characters.
+
+use std::{fmt, sync::Arc};
+
+use fxhash::FxHashMap;
+
+use super::{Instruction, InstructionSet, Opcode, Operand, Program};
+use crate::error::{ParseResult, Source};
+use Opcode::*;
+
+/// Label names and the index of the instruction they point to
+type Labels<'a> = FxHashMap<&'a str, usize>;
+
+/// Splits a line into its label (if any) and its code, dropping the comment
+fn split_line(line: &str) -> (Option<&str>, &str) {
+    let code = match line.find(['#', ';']) {
+        Some(i) => &line[..i],
+        None => line,
+    };
+    match code.find(':') {
+        Some(i) => (Some(code[..i].trim()), code[i + 1..].trim()),
+        None => (None, code.trim()),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Which argument of a built-in opcode is a jump offset
+fn jump_target(opcode: Opcode) -> Option<usize> {
+    match opcode {
+        Jump => Some(0),
+        JumpNotZero | JumpGreaterZero => Some(1),
+        _ => None,
+    }
+}
+
+impl Operand {
+    /// Parses a register, a value or a label, which becomes the offset from the instruction at `index`
+    fn parse(input: &str, index: usize, labels: &Labels, src: &Source) -> ParseResult<Operand> {
+        match input.as_bytes() {
+            b"acc" => Ok(Operand::Register(0)),
+            [c @ b'a'..=b'z'] => Ok(Operand::Register((c - b'a') as usize + 1)),
+            _ if is_identifier(input) => labels
+                .get(input)
+                .map(|&target| Operand::Value(target as isize - index as isize))
+                .ok_or_else(|| src.error(input, "undefined label")),
+            _ => Ok(Operand::Value(src.parse(input)?)),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operand::Register(0) => write!(f, "acc"),
+            Operand::Register(r) => write!(f, "{}", (b'a' + r as u8 - 1) as char),
+            Operand::Value(v) => write!(f, "{:+}", v),
+        }
+    }
+}
+
+/// Formats built-in instructions like they appear in the source.
+/// Custom opcodes only have a name inside their instruction set, so they show up as `custom<index>`
+/// with both arguments: use [`InstructionSet::format`] for those.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.opcode {
+            Custom(i) => write!(f, "custom{} {} {}", i, self.args[0], self.args[1]),
+            _ => f.write_str(&InstructionSet::default().format(self)),
+        }
+    }
+}
+
+impl InstructionSet {
+    /// Formats an instruction like it would appear in the source
+    pub fn format(&self, inst: &Instruction) -> String {
+        let mut res = self.name(inst.opcode).to_string();
+        for arg in &inst.args[..self.arity(inst.opcode)] {
+            res += &format!(" {}", arg);
+        }
+        res
+    }
+
+    fn parse_line(
+        &self,
+        line: &str,
+        index: usize,
+        labels: &Labels,
+        src: &Source,
+    ) -> ParseResult<Instruction> {
+        let mut parts = line.split_whitespace().map(|p| p.trim_end_matches(','));
+        let name = src.expect(parts.next(), line, "an opcode")?;
+        let (opcode, arity) = self
+            .lookup(name)
+            .ok_or_else(|| src.error(name, "unknown opcode"))?;
+        let mut args = [Operand::Value(0); 2];
+        for arg in args.iter_mut().take(arity) {
+            let part = src.expect(parts.next(), line, "an argument")?;
+            *arg = Operand::parse(part, index, labels, src)?;
+        }
+        if let Some(extra) = parts.next() {
+            return Err(src.error(extra, format!("`{}` takes {} argument(s)", name, arity)));
+        }
+        let inst = Instruction { opcode, args };
+        self.check(&inst)
+            .map_err(|e| src.error(line, e.to_string()))?;
+        Ok(inst)
+    }
+
+    /// Assembles a program using this instruction set, reporting errors as part of the given day's input
+    pub fn parse(self: &Arc<Self>, input: &str, day: u8) -> ParseResult<Program> {
+        let src = Source::new(day, input);
+        // First pass: find where every label points to
+        let mut labels = Labels::default();
+        let mut index = 0;
+        for line in input.lines() {
+            let (label, code) = split_line(line);
+            if let Some(label) = label {
+                if !is_identifier(label) {
+                    return Err(src.error(label, "invalid label name"));
+                }
+                if label == "acc" || label.len() == 1 {
+                    return Err(src.error(label, "labels can't be named like a register"));
+                }
+                if labels.insert(label, index).is_some() {
+                    return Err(src.error(label, "duplicate label"));
+                }
+            }
+            if !code.is_empty() {
+                index += 1;
+            }
+        }
+        let codes: Vec<&str> = input
+            .lines()
+            .map(|l| split_line(l).1)
+            .filter(|code| !code.is_empty())
+            .collect();
+        let insts = codes
+            .iter()
+            .enumerate()
+            .map(|(i, code)| self.parse_line(code, i, &labels, &src))
+            .collect::<ParseResult<_>>()?;
+        let spans = codes
+            .iter()
+            .map(|code| {
+                let start = code.as_ptr() as usize - input.as_ptr() as usize;
+                start..start + code.len()
+            })
+            .collect();
+        Ok(Program {
+            instructions: insts,
+            set: self.clone(),
+            spans,
+        })
+    }
+}
+
+impl Program {
+    /// Writes the program back as source, replacing the constant jump offsets
+    /// that land inside the program (or right after it) with labels named `l<index>`
+    pub fn disassemble_with_labels(&self) -> String {
+        let len = self.instructions.len();
+        let targets = |i: usize, inst: &Instruction| {
+            jump_target(inst.opcode)
+                .and_then(|arg| match inst.args[arg] {
+                    Operand::Value(v) => Some((arg, i as isize + v)),
+                    Operand::Register(_) => None,
+                })
+                .filter(|&(_, target)| target >= 0 && target as usize <= len)
+        };
+        let mut labelled = vec![false; len + 1];
+        for (i, inst) in self.instructions.iter().enumerate() {
+            if let Some((_, target)) = targets(i, inst) {
+                labelled[target as usize] = true;
+            }
+        }
+        let mut res = String::new();
+        for (i, inst) in self.instructions.iter().enumerate() {
+            if labelled[i] {
+                res += &format!("l{}:\n", i);
+            }
+            let mut parts = vec![self.set.name(inst.opcode).to_string()];
+            for (arg, operand) in inst.args[..self.set.arity(inst.opcode)].iter().enumerate() {
+                match targets(i, inst) {
+                    Some((jump_arg, target)) if jump_arg == arg => {
+                        parts.push(format!("l{}", target))
+                    }
+                    _ => parts.push(operand.to_string()),
+                }
+            }
+            res += &parts.join(" ");
+            res.push('\n');
+        }
+        if labelled[len] {
+            res += &format!("l{}:\n", len);
+        }
+        res
+    }
+}
+
+/// Writes the program as canonical source, one instruction per line with relative offsets
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for inst in &self.instructions {
+            writeln!(f, "{}", self.set.format(inst))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELLED: &str = "# counts down from 3
+    set a 3
+loop: sub a 1 ; decrement
+    acc +2
+    jnz a loop
+
+end:";
+
+    #[test]
+    fn labels_and_comments() {
+        let program = Program::from_input(LABELLED, 0).unwrap();
+        assert_eq!(program.instructions().len(), 4);
+        assert_eq!(
+            program.instructions()[3],
+            Instruction::new(JumpNotZero, &[Operand::Register(1), Operand::Value(-2)]).unwrap()
+        );
+        let mut machine = program.machine();
+        assert_eq!(machine.run(|_| {}), 0);
+        assert_eq!(machine.accumulator(), 6);
+        assert!(Program::from_input("jmp nowhere", 0).is_err());
+        assert!(Program::from_input("ab:\nab: nop +0", 0).is_err());
+        assert!(Program::from_input("b: nop +0", 0).is_err());
+        let spans: Vec<_> = (0..5).map(|i| program.span(i)).collect();
+        assert_eq!(&LABELLED[spans[1].clone().unwrap()], "sub a 1");
+        assert_eq!(spans[3], Some(74..84));
+        assert_eq!(spans[4], None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let program = Program::from_input(LABELLED, 0).unwrap();
+        let source = program.to_string();
+        assert_eq!(source, "set a +3\nsub a +1\nacc +2\njnz a -2\n");
+        let reassembled = Program::from_input(&source, 0).unwrap();
+        assert_eq!(reassembled.instructions(), program.instructions());
+
+        let program = Program::from_input("nop +0\njmp +2\njmp -2\nacc +1\njmp +1", 0).unwrap();
+        let source = program.disassemble_with_labels();
+        assert_eq!(
+            source,
+            "l0:\nnop +0\njmp l3\njmp l0\nl3:\nacc +1\njmp l5\nl5:\n"
+        );
+        let reassembled = Program::from_input(&source, 0).unwrap();
+        assert_eq!(reassembled.instructions(), program.instructions());
+    }
+
+    #[test]
+    fn display_instruction() {
+        let inst = Instruction::new(Set, &[Operand::Register(0), Operand::Value(-4)]).unwrap();
+        assert_eq!(inst.to_string(), "set acc -4");
+        assert_eq!(
+            Instruction::new(Custom(1), &[]).unwrap().to_string(),
+            "custom1 +0 +0"
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::io::{self, Write};
+
+use fxhash::FxHashSet;
+
+use super::{Instruction, Machine, Opcode, Operand, Program, Registers, REGISTERS};
+use crate::json_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops before running the instruction at this index
+    Index(usize),
+    /// Stops before running any instruction with this opcode
+    Opcode(Opcode),
+}
+
+/// Why the debugger gave control back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// A single instruction ran
+    Step,
+    /// The instruction at this index matches a breakpoint, and didn't run yet
+    Breakpoint(usize),
+    /// The last instruction changed the accumulator
+    Watchpoint {
+        old: isize,
+        new: isize,
+    },
+    /// The instruction at this index is about to run again with the same registers
+    /// (or at all, if the control flow is static), so the program will never terminate
+    Loop(usize),
+    Terminated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub step: usize,
+    pub index: usize,
+    pub instruction: Instruction,
+    /// The registers after running the instruction
+    pub registers: Registers,
+}
+
+/// Runs a program step by step, recording every instruction that runs
+pub struct Debugger<'a> {
+    machine: Machine<'a>,
+    breakpoints: Vec<Breakpoint>,
+    watch_accumulator: bool,
+    trace: Vec<TraceEntry>,
+    /// Whether the control flow doesn't depend on the registers (see [`Program::analyze`])
+    static_flow: bool,
+    /// The breakpoint the debugger is stopped at, which resuming runs past
+    stopped_at: Option<usize>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a Program) -> Debugger<'a> {
+        Debugger {
+            machine: program.machine(),
+            breakpoints: vec![],
+            watch_accumulator: false,
+            trace: vec![],
+            static_flow: program.analyze().is_ok(),
+            stopped_at: None,
+        }
+    }
+
+    pub fn program(&self) -> &'a Program {
+        self.machine.program()
+    }
+
+    /// The machine running the program, to inspect its state
+    pub fn machine(&self) -> &Machine<'a> {
+        &self.machine
+    }
+
+    /// Goes back to the start of the program, keeping the breakpoints and clearing the trace
+    pub fn restart(&mut self) {
+        self.machine.reset();
+        self.trace.clear();
+        self.stopped_at = None;
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Returns whether the breakpoint existed
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|b| *b != breakpoint);
+        self.breakpoints.len() != len
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Stops whenever an instruction changes the accumulator
+    pub fn watch_accumulator(&mut self, watch: bool) {
+        self.watch_accumulator = watch;
+    }
+
+    fn breakpoint_at(&self, index: isize) -> Option<Stop> {
+        let inst = self.machine.instruction(index)?;
+        self.breakpoints
+            .iter()
+            .any(|b| match *b {
+                Breakpoint::Index(i) => i as isize == index,
+                Breakpoint::Opcode(op) => inst.opcode == op,
+            })
+            .then_some(Stop::Breakpoint(index as usize))
+    }
+
+    /// Runs a single instruction, ignoring breakpoints
+    pub fn step(&mut self) -> Stop {
+        self.stopped_at = None;
+        let index = self.machine.instruction_ptr();
+        let old = self.machine.accumulator();
+        let instruction = match self.machine.step() {
+            Some(inst) => inst,
+            None => return Stop::Terminated,
+        };
+        let registers = self.machine.registers();
+        self.trace.push(TraceEntry {
+            step: self.trace.len(),
+            index: index as usize,
+            instruction,
+            registers,
+        });
+        if self.watch_accumulator && registers.accumulator() != old {
+            Stop::Watchpoint {
+                old,
+                new: registers.accumulator(),
+            }
+        } else if self.machine.terminated() {
+            Stop::Terminated
+        } else {
+            Stop::Step
+        }
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit, or the program terminates or loops forever.
+    /// Resuming from a breakpoint runs the instruction it stopped at, instead of stopping right away.
+    ///
+    /// With a static control flow, a loop is found as soon as an instruction runs twice.
+    /// Otherwise the whole machine state is compared with an earlier one (Brent's algorithm),
+    /// which keeps memory constant but can report the loop a few times around.
+    pub fn resume(&mut self) -> Stop {
+        let stopped_at = self.stopped_at.take();
+        let mut known = FxHashSet::default();
+        let mut earlier = self.machine.snapshot();
+        let (mut power, mut steps) = (1, 0);
+        loop {
+            let index = self.machine.instruction_ptr();
+            if steps > 0 || stopped_at != Some(index as usize) {
+                if let Some(stop) = self.breakpoint_at(index) {
+                    self.stopped_at = Some(index as usize);
+                    return stop;
+                }
+            }
+            if self.static_flow {
+                if !known.insert(index) {
+                    return Stop::Loop(index as usize);
+                }
+            } else if steps > 0 {
+                let state = self.machine.snapshot();
+                if state == earlier {
+                    return Stop::Loop(index as usize);
+                }
+                if steps == power {
+                    earlier = state;
+                    power *= 2;
+                    steps = 0;
+                }
+            }
+            steps += 1;
+            match self.step() {
+                Stop::Step => {}
+                stop => return stop,
+            }
+        }
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    pub fn clear_trace(&mut self) {
+        self.trace.clear();
+    }
+
+    /// Writes the trace as JSON lines, only listing the letter registers that aren't 0
+    pub fn write_trace<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let set = self.program().instruction_set();
+        for entry in &self.trace {
+            let registers: Vec<String> = (1..REGISTERS)
+                .map(Operand::Register)
+                .filter(|&r| entry.registers.get(r) != 0)
+                .map(|r| format!("\"{}\":{}", r, entry.registers.get(r)))
+                .collect();
+            writeln!(
+                out,
+                "{{\"step\":{},\"index\":{},\"instruction\":{},\"acc\":{},\"registers\":{{{}}}}}",
+                entry.step,
+                entry.index,
+                json_string(&set.format(&entry.instruction)),
+                entry.registers.accumulator(),
+                registers.join(",")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOOPING: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+    #[test]
+    fn breakpoints() {
+        let program = Program::from_input(LOOPING, 0).unwrap();
+        let mut debugger = Debugger::new(&program);
+        debugger.add_breakpoint(Breakpoint::Index(6));
+        debugger.add_breakpoint(Breakpoint::Opcode(Opcode::Jump));
+        assert_eq!(debugger.resume(), Stop::Breakpoint(2));
+        assert_eq!(debugger.resume(), Stop::Breakpoint(6));
+        assert!(debugger.remove_breakpoint(Breakpoint::Opcode(Opcode::Jump)));
+        assert_eq!(debugger.resume(), Stop::Breakpoint(6));
+        assert_eq!(debugger.machine().accumulator(), 6);
+        assert_eq!(debugger.trace().len(), 9);
+        debugger.restart();
+        assert_eq!(debugger.resume(), Stop::Breakpoint(6));
+        assert_eq!(debugger.trace().len(), 3);
+
+        // A breakpoint where the run starts still stops it, but only once
+        debugger.add_breakpoint(Breakpoint::Index(0));
+        debugger.restart();
+        assert_eq!(debugger.resume(), Stop::Breakpoint(0));
+        assert_eq!(debugger.resume(), Stop::Breakpoint(6));
+        assert_eq!(debugger.trace().len(), 3);
+    }
+
+    #[test]
+    fn watch_and_loop() {
+        let program = Program::from_input(LOOPING, 0).unwrap();
+        let mut debugger = Debugger::new(&program);
+        debugger.watch_accumulator(true);
+        assert_eq!(debugger.resume(), Stop::Watchpoint { old: 0, new: 1 });
+        assert_eq!(debugger.step(), Stop::Step);
+        debugger.watch_accumulator(false);
+        assert_eq!(debugger.resume(), Stop::Loop(6));
+        let program = Program::from_input("set a 3\nsub a 1\njnz 1 -1", 0).unwrap();
+        let mut debugger = Debugger::new(&program);
+        // Register `a` keeps changing, but not for long
+        assert_eq!(debugger.resume(), Stop::Loop(1));
+
+        // The jump depends on `b`, and the registers come back after 3 rounds
+        let program = Program::from_input("set b 1\nadd a 1\nmod a 3\njnz b -2", 0).unwrap();
+        let mut debugger = Debugger::new(&program);
+        assert!(matches!(debugger.resume(), Stop::Loop(1..=3)));
+        assert!(debugger.trace().len() < 30);
+    }
+
+    #[test]
+    fn json_trace() {
+        let program = Program::from_input("set a 2\nacc a", 0).unwrap();
+        let mut debugger = Debugger::new(&program);
+        assert_eq!(debugger.resume(), Stop::Terminated);
+        let mut out = vec![];
+        debugger.write_trace(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"step":0,"index":0,"instruction":"set a +2","acc":0,"registers":{"a":2}}
+{"step":1,"index":1,"instruction":"acc a","acc":2,"registers":{"a":2}}
+"#
+        );
+    }
+}
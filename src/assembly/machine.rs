@@ -0,0 +1,212 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use fxhash::FxHashSet;
+
+use super::{Flow, Instruction, InstructionError, Program, Registers};
+
+/// Where a machine is in a run, which can be restored later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Snapshot {
+    pub instruction_ptr: isize,
+    pub registers: Registers,
+}
+
+/// Stops a running machine, possibly from another thread
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The state of a single run of a program.
+/// The program is only borrowed, so any number of machines (on any number of threads) can run it at once.
+pub struct Machine<'a> {
+    program: &'a Program,
+    state: Snapshot,
+    /// Replaces the instruction at an index, without touching the program
+    patch: Option<(usize, Instruction)>,
+    stop: StopHandle,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(program: &'a Program) -> Machine<'a> {
+        Machine {
+            program,
+            state: Snapshot::default(),
+            patch: None,
+            stop: StopHandle::default(),
+        }
+    }
+
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+
+    /// Runs `instruction` instead of the one at `index`, replacing any earlier patch.
+    /// Fails if the instruction can't run with the program's instruction set.
+    pub fn patch(
+        &mut self,
+        index: usize,
+        instruction: Instruction,
+    ) -> Result<(), InstructionError> {
+        self.program.set.check(&instruction)?;
+        self.patch = Some((index, instruction));
+        Ok(())
+    }
+
+    pub fn clear_patch(&mut self) {
+        self.patch = None;
+    }
+
+    /// The instruction at `index`, with the patch applied
+    pub fn instruction(&self, index: isize) -> Option<Instruction> {
+        if index < 0 {
+            return None;
+        }
+        match self.patch {
+            Some((i, inst)) if i == index as usize => Some(inst),
+            _ => self.program.instructions.get(index as usize).copied(),
+        }
+    }
+
+    /// Runs until the program terminates (returning 0) or the machine is stopped (returning 1)
+    pub fn run<F>(&mut self, mut callback: F) -> i32
+    where
+        F: FnMut(&Instruction),
+    {
+        while !self.stop.stopped() {
+            match self.instruction(self.state.instruction_ptr) {
+                Some(inst) => callback(&inst),
+                None => return 0, // Program terminated naturally
+            }
+            self.step();
+        }
+        1 // Program was manually stopped
+    }
+
+    /// Runs until the program terminates, returning the accumulator.
+    /// Returns `None` if the machine gets back to a state it was already in (so it would loop forever),
+    /// or if it didn't terminate within `max_steps` instructions.
+    pub fn run_to_end(&mut self, max_steps: usize) -> Option<isize> {
+        let mut known = FxHashSet::default();
+        for _ in 0..max_steps {
+            if self.terminated() {
+                return Some(self.accumulator());
+            }
+            if !known.insert(self.state) {
+                return None;
+            }
+            self.step();
+        }
+        None
+    }
+
+    /// Whether the instruction pointer left the program
+    pub fn terminated(&self) -> bool {
+        let inst_ptr = self.state.instruction_ptr;
+        inst_ptr < 0 || inst_ptr as usize >= self.program.instructions.len()
+    }
+
+    /// Runs a single instruction and returns it, or `None` if the program already terminated
+    pub fn step(&mut self) -> Option<Instruction> {
+        let inst_ptr = self.state.instruction_ptr;
+        let inst = self.instruction(inst_ptr)?;
+        let flow = inst.run(&mut self.state.registers, &self.program.set);
+        self.state.instruction_ptr = match flow {
+            Flow::Next => inst_ptr.wrapping_add(1),
+            Flow::Jump(offset) => inst_ptr.wrapping_add(offset),
+            // Move past the end, so the program is considered terminated from now on
+            Flow::Halt => self.program.instructions.len() as isize,
+        };
+        Some(inst)
+    }
+
+    /// A handle that stops [`Machine::run`], which can be used from the callback or from another thread
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.state
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.state = snapshot;
+    }
+
+    /// Goes back to the start of the program with empty registers, and clears the stop flag.
+    /// The patch is kept.
+    pub fn reset(&mut self) {
+        self.state = Snapshot::default();
+        self.stop.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn accumulator(&self) -> isize {
+        self.state.registers.accumulator()
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.state.registers
+    }
+
+    pub fn instruction_ptr(&self) -> isize {
+        self.state.instruction_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::{Opcode, Operand};
+
+    fn assert_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn snapshot_and_reset() {
+        assert_sync::<Program>();
+        let program = Program::from_input("acc +1\nacc +2\nacc +3", 0).unwrap();
+        let mut machine = program.machine();
+        machine.step();
+        let snapshot = machine.snapshot();
+        assert_eq!(machine.run(|_| {}), 0);
+        assert_eq!(machine.accumulator(), 6);
+        machine.restore(snapshot);
+        assert_eq!((machine.instruction_ptr(), machine.accumulator()), (1, 1));
+        let nop = Instruction::new(Opcode::NoOp, &[]).unwrap();
+        assert_eq!(machine.patch(2, nop), Ok(()));
+        let add = Instruction::new(Opcode::Add, &[Operand::Value(1)]).unwrap();
+        assert_eq!(machine.patch(1, add), Err(InstructionError::NotARegister));
+        machine.reset();
+        assert_eq!(machine.run_to_end(10), Some(3));
+    }
+
+    #[test]
+    fn stop_handle() {
+        let program = Program::from_input("acc +1\njmp -1", 0).unwrap();
+        let mut machine = program.machine();
+        let handle = machine.stop_handle();
+        let mut steps = 0;
+        assert_eq!(
+            machine.run(|_| {
+                steps += 1;
+                if steps == 5 {
+                    handle.stop();
+                }
+            }),
+            1
+        );
+        assert_eq!(machine.accumulator(), 3);
+        machine.reset();
+        assert_eq!(machine.run_to_end(100), None);
+    }
+}
@@ -0,0 +1,436 @@
+use std::{error::Error, fmt, ops::Range, sync::Arc};
+
+use crate::error::ParseResult;
+use Opcode::*;
+
+mod analysis;
+mod assembler;
+mod debugger;
+mod machine;
+
+pub use analysis::{DynamicFlow, Outcome, Patch};
+pub use debugger::{Breakpoint, Debugger, Stop, TraceEntry};
+pub use machine::{Machine, Snapshot, StopHandle};
+
+/// The accumulator (`acc`) is register 0, `a` to `z` are registers 1 to 26.
+pub const REGISTERS: usize = 27;
+
+/// Why an instruction can't run with a given instruction set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionError {
+    /// Instructions take at most 2 arguments
+    TooManyArguments(usize),
+    /// A built-in that writes to its first argument was given a value instead of a register
+    NotARegister,
+    /// A register past `z`
+    UnknownRegister(usize),
+    /// A custom opcode that isn't registered in the instruction set
+    UnknownOpcode(usize),
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionError::TooManyArguments(n) => {
+                write!(f, "{} arguments, at most 2 allowed", n)
+            }
+            InstructionError::NotARegister => {
+                write!(f, "expected a register as the first argument")
+            }
+            InstructionError::UnknownRegister(r) => write!(f, "no register {}", r),
+            InstructionError::UnknownOpcode(i) => write!(f, "no custom opcode {}", i),
+        }
+    }
+}
+
+impl Error for InstructionError {}
+
+/// An instruction that can't be part of a program, at `index`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidInstruction {
+    pub index: usize,
+    pub error: InstructionError,
+}
+
+impl fmt::Display for InvalidInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "instruction {}: {}", self.index, self.error)
+    }
+}
+
+impl Error for InvalidInstruction {}
+
+/// The instructions of a program, which never change once assembled.
+/// A program can be shared between threads, each running it on its own [`Machine`].
+#[derive(Default, Clone)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    set: Arc<InstructionSet>,
+    /// Where each instruction's code is in the source it was assembled from, empty for programs built in code
+    spans: Vec<Range<usize>>,
+}
+
+#[derive(Ord, PartialOrd, Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    /// Unused arguments are `Value(0)`
+    pub args: [Operand; 2],
+}
+
+#[derive(Ord, PartialOrd, Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum Opcode {
+    /// `acc x`: adds x to the accumulator
+    Accumulate,
+    /// `jmp x`: jumps by x instructions
+    Jump,
+    /// `nop x`: does nothing
+    NoOp,
+    /// `set r x`: r = x
+    Set,
+    /// `add r x`: r += x
+    Add,
+    /// `sub r x`: r -= x
+    Sub,
+    /// `mul r x`: r *= x
+    Mul,
+    /// `mod r x`: r %= x, halts if x is 0
+    Mod,
+    /// `jnz x y`: jumps by y instructions if x isn't 0
+    JumpNotZero,
+    /// `jgz x y`: jumps by y instructions if x is greater than 0
+    JumpGreaterZero,
+    /// An opcode registered in the program's [`InstructionSet`], by index
+    Custom(usize),
+}
+
+#[derive(Ord, PartialOrd, Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum Operand {
+    Register(usize),
+    Value(isize),
+}
+
+/// What to do with the instruction pointer after running an instruction
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Flow {
+    Next,
+    /// Relative jump
+    Jump(isize),
+    Halt,
+}
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub struct Registers([isize; REGISTERS]);
+
+type CustomRun = dyn Fn(&Instruction, &mut Registers) -> Flow + Send + Sync;
+
+pub struct CustomOpcode {
+    pub name: String,
+    pub arity: usize,
+    run: Box<CustomRun>,
+}
+
+/// The opcodes a program can use: the built-in ones, plus any custom opcode registered by the caller
+#[derive(Default)]
+pub struct InstructionSet {
+    custom: Vec<CustomOpcode>,
+}
+
+const BUILTINS: [(&str, Opcode, usize); 10] = [
+    ("acc", Accumulate, 1),
+    ("jmp", Jump, 1),
+    ("nop", NoOp, 1),
+    ("set", Set, 2),
+    ("add", Add, 2),
+    ("sub", Sub, 2),
+    ("mul", Mul, 2),
+    ("mod", Mod, 2),
+    ("jnz", JumpNotZero, 2),
+    ("jgz", JumpGreaterZero, 2),
+];
+
+impl Operand {
+    pub fn register(self) -> Option<usize> {
+        match self {
+            Operand::Register(r) => Some(r),
+            Operand::Value(_) => None,
+        }
+    }
+}
+
+impl Registers {
+    /// Returns the value of an operand, reading the register if needed
+    pub fn get(&self, operand: Operand) -> isize {
+        match operand {
+            Operand::Register(r) => self.0[r],
+            Operand::Value(v) => v,
+        }
+    }
+
+    pub fn set(&mut self, register: usize, value: isize) {
+        self.0[register] = value;
+    }
+
+    pub fn accumulator(&self) -> isize {
+        self.0[0]
+    }
+}
+
+impl InstructionSet {
+    /// Adds a custom opcode, which takes precedence over any built-in opcode with the same name.
+    /// `run` is only called with instructions that have exactly `arity` arguments (at most 2).
+    pub fn register<F>(&mut self, name: &str, arity: usize, run: F) -> Opcode
+    where
+        F: Fn(&Instruction, &mut Registers) -> Flow + Send + Sync + 'static,
+    {
+        assert!(arity <= 2, "opcodes can have at most 2 arguments");
+        self.custom.push(CustomOpcode {
+            name: name.to_string(),
+            arity,
+            run: Box::new(run),
+        });
+        Custom(self.custom.len() - 1)
+    }
+
+    /// Finds an opcode and its arity by name
+    pub fn lookup(&self, name: &str) -> Option<(Opcode, usize)> {
+        self.custom
+            .iter()
+            .rposition(|c| c.name == name)
+            .map(|i| (Custom(i), self.custom[i].arity))
+            .or_else(|| {
+                BUILTINS
+                    .iter()
+                    .find(|b| b.0 == name)
+                    .map(|&(_, op, arity)| (op, arity))
+            })
+    }
+
+    /// Returns the name of an opcode
+    pub fn name(&self, opcode: Opcode) -> &str {
+        match opcode {
+            Custom(i) => &self.custom[i].name,
+            op => BUILTINS.iter().find(|b| b.1 == op).unwrap().0,
+        }
+    }
+
+    /// Returns how many arguments an opcode takes
+    pub fn arity(&self, opcode: Opcode) -> usize {
+        match opcode {
+            Custom(i) => self.custom[i].arity,
+            op => BUILTINS.iter().find(|b| b.1 == op).unwrap().2,
+        }
+    }
+
+    /// Checks that an instruction can run with this set without crashing the machine
+    pub fn check(&self, inst: &Instruction) -> Result<(), InstructionError> {
+        if let Custom(i) = inst.opcode {
+            if i >= self.custom.len() {
+                return Err(InstructionError::UnknownOpcode(i));
+            }
+        }
+        for arg in &inst.args {
+            match arg.register() {
+                Some(r) if r >= REGISTERS => return Err(InstructionError::UnknownRegister(r)),
+                _ => {}
+            }
+        }
+        // Built-ins that write to a register need one as their first argument
+        if let Set | Add | Sub | Mul | Mod = inst.opcode {
+            if inst.args[0].register().is_none() {
+                return Err(InstructionError::NotARegister);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Instruction {
+    /// Fails with more than 2 arguments, missing ones are `Value(0)`
+    pub fn new(opcode: Opcode, args: &[Operand]) -> Result<Instruction, InstructionError> {
+        if args.len() > 2 {
+            return Err(InstructionError::TooManyArguments(args.len()));
+        }
+        let mut inst = Instruction {
+            opcode,
+            args: [Operand::Value(0); 2],
+        };
+        inst.args[..args.len()].copy_from_slice(args);
+        Ok(inst)
+    }
+
+    /// Stores `f(x, y)` in the register `x`, which [`InstructionSet::check`] made sure of
+    fn apply(&self, regs: &mut Registers, f: fn(isize, isize) -> isize) -> Flow {
+        let [x, y] = self.args;
+        regs.set(x.register().unwrap(), f(regs.get(x), regs.get(y)));
+        Flow::Next
+    }
+
+    /// Arithmetic wraps around on overflow, so no program can crash the machine
+    fn run(&self, regs: &mut Registers, set: &InstructionSet) -> Flow {
+        let [x, y] = self.args;
+        match self.opcode {
+            Accumulate => {
+                regs.set(0, regs.accumulator().wrapping_add(regs.get(x)));
+                Flow::Next
+            }
+            Jump => Flow::Jump(regs.get(x)),
+            NoOp => Flow::Next,
+            Set => self.apply(regs, |_, b| b),
+            Add => self.apply(regs, isize::wrapping_add),
+            Sub => self.apply(regs, isize::wrapping_sub),
+            Mul => self.apply(regs, isize::wrapping_mul),
+            Mod if regs.get(y) == 0 => Flow::Halt,
+            // `isize::MIN % -1` overflows too
+            Mod => self.apply(regs, isize::wrapping_rem_euclid),
+            JumpNotZero if regs.get(x) != 0 => Flow::Jump(regs.get(y)),
+            JumpGreaterZero if regs.get(x) > 0 => Flow::Jump(regs.get(y)),
+            JumpNotZero | JumpGreaterZero => Flow::Next,
+            Custom(i) => (set.custom[i].run)(self, regs),
+        }
+    }
+}
+
+impl Program {
+    /// A program with the built-in opcodes, failing at the first instruction that can't run
+    pub fn with(instructions: Vec<Instruction>) -> Result<Program, InvalidInstruction> {
+        Program::with_set(instructions, Arc::default())
+    }
+
+    /// A program with the given opcodes, failing at the first instruction that can't run with them
+    pub fn with_set(
+        instructions: Vec<Instruction>,
+        set: Arc<InstructionSet>,
+    ) -> Result<Program, InvalidInstruction> {
+        for (index, inst) in instructions.iter().enumerate() {
+            set.check(inst)
+                .map_err(|error| InvalidInstruction { index, error })?;
+        }
+        Ok(Program {
+            instructions,
+            set,
+            spans: vec![],
+        })
+    }
+
+    /// Assembles a program with the built-in opcodes, reporting errors as part of the given day's input
+    pub fn from_input(input: &str, day: u8) -> ParseResult<Program> {
+        Arc::new(InstructionSet::default()).parse(input, day)
+    }
+
+    /// A new machine that runs this program from the start
+    pub fn machine(&self) -> Machine<'_> {
+        Machine::new(self)
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet {
+        &self.set
+    }
+
+    /// The byte range of an instruction's code (without its label or comment) in the assembled source
+    pub fn span(&self, index: usize) -> Option<Range<usize>> {
+        self.spans.get(index).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers_and_jumps() {
+        // a = 5!
+        let program = Program::from_input(
+            "set a 5
+set b 1
+mul b a
+sub a 1
+jnz a -2
+set acc b",
+            0,
+        )
+        .unwrap();
+        let mut machine = program.machine();
+        assert_eq!(machine.run(|_| {}), 0);
+        assert_eq!(machine.accumulator(), 120);
+        assert_eq!(machine.registers().get(Operand::Register(1)), 0);
+    }
+
+    #[test]
+    fn custom_opcode() {
+        let mut set = InstructionSet::default();
+        set.register("tpl", 1, |inst, regs| {
+            let r = inst.args[0].register().unwrap();
+            regs.set(r, regs.get(inst.args[0]) * 3);
+            Flow::Next
+        });
+        set.register("hlt", 0, |_, _| Flow::Halt);
+        let program = Arc::new(set)
+            .parse("acc +2\ntpl acc\nhlt\nacc +1", 0)
+            .unwrap();
+        let mut machine = program.machine();
+        assert_eq!(machine.run(|_| {}), 0);
+        assert_eq!(machine.accumulator(), 6);
+    }
+
+    #[test]
+    fn invalid_programs() {
+        assert!(Program::from_input("foo +1", 0).is_err());
+        assert!(Program::from_input("acc", 0).is_err());
+        assert!(Program::from_input("acc +1 +2", 0).is_err());
+        assert!(Program::from_input("add 1 2", 0).is_err());
+    }
+
+    #[test]
+    fn invalid_instructions() {
+        let (r, v) = (Operand::Register, Operand::Value);
+        assert_eq!(
+            Instruction::new(Set, &[r(1), v(1), v(2)]),
+            Err(InstructionError::TooManyArguments(3))
+        );
+        let invalid =
+            |inst: Instruction| Program::with(vec![Instruction::new(NoOp, &[]).unwrap(), inst]);
+        let err = invalid(Instruction::new(Add, &[v(1), v(2)]).unwrap())
+            .err()
+            .unwrap();
+        assert_eq!((err.index, err.error), (1, InstructionError::NotARegister));
+        let err = invalid(Instruction::new(Custom(0), &[]).unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(err.error, InstructionError::UnknownOpcode(0));
+        let err = invalid(Instruction::new(Jump, &[r(REGISTERS)]).unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "instruction 1: no register 27");
+
+        let mut set = InstructionSet::default();
+        let tpl = set.register("tpl", 1, |_, _| Flow::Next);
+        let inst = Instruction::new(tpl, &[r(0)]).unwrap();
+        assert!(Program::with(vec![inst]).is_err());
+        assert!(Program::with_set(vec![inst], Arc::new(set)).is_ok());
+    }
+
+    #[test]
+    fn overflow() {
+        let program = Program::from_input(
+            "acc +9223372036854775807
+acc +1
+set a -9223372036854775808
+mul a -1
+sub a 1
+mod a -1
+jmp +9223372036854775807",
+            0,
+        )
+        .unwrap();
+        let mut machine = program.machine();
+        assert_eq!(machine.run(|_| {}), 0);
+        assert_eq!(machine.accumulator(), isize::MIN);
+        assert_eq!(machine.registers().get(Operand::Register(1)), 0);
+        assert!(machine.terminated());
+        assert!(crate::solve(8, crate::Part::One, "acc +9223372036854775807\nacc +1").is_ok());
+    }
+}
@@ -0,0 +1,381 @@
+//! Benchmarks every part at once, spread over a few threads,
+//! and compares the timings with an earlier report to catch slowdowns.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{ParseResult, Source},
+    runner::format_duration,
+    solver::{self, Part, SolverError},
+};
+
+/// The timings of a part over every iteration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    pub iterations: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+impl Measurement {
+    /// `times` must not be empty
+    pub fn from_times(mut times: Vec<Duration>) -> Measurement {
+        times.sort();
+        let n = times.len();
+        let median = if n % 2 == 1 {
+            times[n / 2]
+        } else {
+            (times[n / 2 - 1] + times[n / 2]) / 2
+        };
+        Measurement {
+            iterations: n,
+            min: times[0],
+            median,
+            max: times[n - 1],
+        }
+    }
+}
+
+/// A part that got slower (or faster), by median time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub day: u8,
+    pub part: Part,
+    pub before: Duration,
+    pub after: Duration,
+}
+
+impl Change {
+    /// How much the median time changed, e.g. `0.25` for 25% slower
+    pub fn relative(&self) -> f64 {
+        self.after.as_secs_f64() / self.before.as_secs_f64().max(f64::MIN_POSITIVE) - 1.0
+    }
+}
+
+const FIELDS: [&str; 6] = ["day", "part", "iterations", "min_ns", "median_ns", "max_ns"];
+
+/// The measurements of a benchmark run, by day and part
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report(BTreeMap<(u8, Part), Measurement>);
+
+impl Report {
+    pub fn get(&self, day: u8, part: Part) -> Option<&Measurement> {
+        self.0.get(&(day, part))
+    }
+
+    pub fn insert(&mut self, day: u8, part: Part, measurement: Measurement) {
+        self.0.insert((day, part), measurement);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u8, Part, &Measurement)> {
+        self.0.iter().map(|(&(day, part), m)| (day, part, m))
+    }
+
+    fn rows(&self) -> impl Iterator<Item = [u128; 6]> + '_ {
+        self.iter().map(|(day, part, m)| {
+            [
+                day as u128,
+                part.number() as u128,
+                m.iterations as u128,
+                m.min.as_nanos(),
+                m.median.as_nanos(),
+                m.max.as_nanos(),
+            ]
+        })
+    }
+
+    /// A JSON array with one object per part. Times are in nanoseconds.
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self
+            .rows()
+            .map(|row| {
+                let fields: Vec<String> = FIELDS
+                    .iter()
+                    .zip(&row)
+                    .map(|(name, value)| format!("\"{}\":{}", name, value))
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect();
+        format!("[{}]\n", entries.join(",\n"))
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut res = FIELDS.join(",") + "\n";
+        for row in self.rows() {
+            let row: Vec<String> = row.iter().map(u128::to_string).collect();
+            res += &row.join(",");
+            res.push('\n');
+        }
+        res
+    }
+
+    /// Reads a report written by [`Report::to_json`] or [`Report::to_csv`]
+    pub fn parse(input: &str) -> ParseResult<Report> {
+        let src = Source::new(0, input);
+        let trimmed = input.trim();
+        let mut report = Report::default();
+        if let Some(array) = trimmed.strip_prefix('[') {
+            let array = src.expect(array.strip_suffix(']'), trimmed, "`]`")?;
+            for object in array.split('}') {
+                let object = object.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+                if object.is_empty() {
+                    continue;
+                }
+                let object = object
+                    .strip_prefix('{')
+                    .ok_or_else(|| src.error(object, "expected `{`"))?;
+                let mut values = [None; 6];
+                for pair in object.split(',') {
+                    let (key, value) = pair
+                        .split_once(':')
+                        .ok_or_else(|| src.error(pair, "expected `\"<key>\":<value>`"))?;
+                    let key = key.trim().trim_matches('"');
+                    let idx = FIELDS
+                        .iter()
+                        .position(|&f| f == key)
+                        .ok_or_else(|| src.error(key, "unknown field"))?;
+                    values[idx] = Some(src.parse(value.trim())?);
+                }
+                let mut row = [0; 6];
+                for (i, value) in values.iter().enumerate() {
+                    row[i] = src.expect(*value, object, &format!("`{}`", FIELDS[i]))?;
+                }
+                report.insert_row(&src, object, row)?;
+            }
+        } else {
+            let mut lines = trimmed.lines();
+            let header = src.expect(lines.next(), input, "a header")?;
+            if header.trim() != FIELDS.join(",") {
+                return Err(src.error(header, format!("expected `{}`", FIELDS.join(","))));
+            }
+            for line in lines {
+                let values: Vec<&str> = line.trim().split(',').collect();
+                if values.len() != FIELDS.len() {
+                    return Err(src.error(line, format!("expected {} values", FIELDS.len())));
+                }
+                let mut row = [0; 6];
+                for (i, value) in values.into_iter().enumerate() {
+                    row[i] = src.parse(value)?;
+                }
+                report.insert_row(&src, line, row)?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn insert_row(&mut self, src: &Source, fragment: &str, row: [u128; 6]) -> ParseResult<()> {
+        let [day, part, iterations, min, median, max] = row;
+        let part = match part {
+            1 => Part::One,
+            2 => Part::Two,
+            _ => return Err(src.error(fragment, "the part must be 1 or 2")),
+        };
+        let day = u8::try_from(day).map_err(|_| src.error(fragment, "day out of range"))?;
+        let nanos = |n: u128| Duration::from_nanos(n as u64);
+        self.insert(
+            day,
+            part,
+            Measurement {
+                iterations: iterations as usize,
+                min: nanos(min),
+                median: nanos(median),
+                max: nanos(max),
+            },
+        );
+        Ok(())
+    }
+
+    /// How the median time of each part in both reports changed since `before`
+    pub fn compare(&self, before: &Report) -> Vec<Change> {
+        self.iter()
+            .filter_map(|(day, part, after)| {
+                let before = before.get(day, part)?;
+                Some(Change {
+                    day,
+                    part,
+                    before: before.median,
+                    after: after.median,
+                })
+            })
+            .collect()
+    }
+
+    /// The parts that got slower than `before` by more than `threshold` (`0.1` is 10%)
+    pub fn regressions(&self, before: &Report, threshold: f64) -> Vec<Change> {
+        self.compare(before)
+            .into_iter()
+            .filter(|c| c.relative() > threshold)
+            .collect()
+    }
+
+    /// A plain text table, with the change since `before` if there's an earlier report
+    pub fn table(&self, before: Option<&Report>) -> String {
+        let changes: BTreeMap<_, _> = before
+            .map(|b| self.compare(b))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| ((c.day, c.part), c.relative()))
+            .collect();
+        let mut res = format!(
+            "{:<5}  {:>12}  {:>12}  {:>12}{}\n",
+            "day",
+            "min",
+            "median",
+            "max",
+            if before.is_some() { "    change" } else { "" }
+        );
+        for (day, part, m) in self.iter() {
+            write!(
+                res,
+                "{:>2}/{}   {:>12}  {:>12}  {:>12}",
+                day,
+                part,
+                format_duration(m.min),
+                format_duration(m.median),
+                format_duration(m.max)
+            )
+            .unwrap();
+            if let Some(change) = changes.get(&(day, part)) {
+                write!(res, "  {:>+7.1}%", change * 100.0).unwrap();
+            }
+            res.push('\n');
+        }
+        res
+    }
+}
+
+fn measure(
+    day: u8,
+    part: Part,
+    input: &str,
+    iterations: usize,
+) -> Result<Measurement, SolverError> {
+    let mut times = Vec::with_capacity(iterations);
+    for _ in 0..iterations.max(1) {
+        let start = Instant::now();
+        solver::solve(day, part, input)?;
+        times.push(start.elapsed());
+    }
+    Ok(Measurement::from_times(times))
+}
+
+/// Runs each part `iterations` times with the registered solutions, spreading the parts over `threads` threads.
+/// Stops at the first part that fails.
+pub fn run(
+    parts: &[(u8, Part, &str)],
+    iterations: usize,
+    threads: usize,
+) -> Result<Report, SolverError> {
+    // The index of the next part to pick up
+    let next = AtomicUsize::new(0);
+    let report = Mutex::new(Report::default());
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| -> Result<(), SolverError> {
+                    while let Some(&(day, part, input)) =
+                        parts.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let measurement = measure(day, part, input, iterations)?;
+                        report.lock().unwrap().insert(day, part, measurement);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        handles.into_iter().try_for_each(|h| h.join().unwrap())
+    })?;
+    Ok(report.into_inner().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Report {
+        let ms = Duration::from_millis;
+        let mut report = Report::default();
+        report.insert(
+            1,
+            Part::One,
+            Measurement::from_times(vec![ms(3), ms(1), ms(2)]),
+        );
+        report.insert(
+            23,
+            Part::Two,
+            Measurement::from_times(vec![ms(10), ms(40), ms(20), ms(30)]),
+        );
+        report
+    }
+
+    #[test]
+    fn measurements() {
+        let report = example();
+        let m = report.get(23, Part::Two).unwrap();
+        assert_eq!(m.iterations, 4);
+        assert_eq!(
+            (m.min, m.median, m.max),
+            (
+                Duration::from_millis(10),
+                Duration::from_millis(25),
+                Duration::from_millis(40)
+            )
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let report = example();
+        assert_eq!(Report::parse(&report.to_json()), Ok(report.clone()));
+        assert_eq!(Report::parse(&report.to_csv()), Ok(report.clone()));
+        assert_eq!(
+            report.to_csv().lines().nth(1),
+            Some("1,1,3,1000000,2000000,3000000")
+        );
+        let err = Report::parse("day,part\n1,1").unwrap_err();
+        assert_eq!(err.line, 1);
+        let err = Report::parse("[{\"day\":1,\"part\":3}]").unwrap_err();
+        assert_eq!(err.reason, "expected `iterations`");
+    }
+
+    #[test]
+    fn regressions() {
+        let before = example();
+        let mut after = example();
+        let slower = Measurement {
+            median: Duration::from_millis(3),
+            ..*before.get(1, Part::One).unwrap()
+        };
+        after.insert(1, Part::One, slower);
+        after.insert(2, Part::One, slower);
+        let changes = after.compare(&before);
+        assert_eq!(changes.len(), 2);
+        assert!((changes[0].relative() - 0.5).abs() < 1e-9);
+        let regressions = after.regressions(&before, 0.1);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!((regressions[0].day, regressions[0].part), (1, Part::One));
+        assert!(after.table(Some(&before)).contains("+50.0%"));
+    }
+
+    #[test]
+    fn parallel_run() {
+        let input: &str = example!(1721 979 366 299 675 1456);
+        let parts = [(1, Part::One, input), (1, Part::Two, input)];
+        let report = run(&parts, 3, 2).unwrap();
+        assert_eq!(report.iter().count(), 2);
+        assert!(report.iter().all(|(_, _, m)| m.iterations == 3));
+        assert!(run(&[(1, Part::One, "x")], 1, 1).is_err());
+    }
+}
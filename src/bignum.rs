@@ -0,0 +1,124 @@
+//! Arbitrarily large unsigned integers, for counts that don't fit in a `u128`.
+//! Only what the puzzles need: addition and printing.
+
+use std::{
+    fmt,
+    ops::{Add, AddAssign},
+};
+
+/// Stored as base 2^32 digits, least significant first, without trailing zeros
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint(Vec<u32>);
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `None` if it's too large
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.0.len() > 4 {
+            return None;
+        }
+        Some(
+            self.0
+                .iter()
+                .rev()
+                .fold(0, |acc, &digit| acc << 32 | digit as u128),
+        )
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> BigUint {
+        let mut digits = vec![n as u32, (n >> 32) as u32];
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigUint(digits)
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, rhs: &BigUint) {
+        if self.0.len() < rhs.0.len() {
+            self.0.resize(rhs.0.len(), 0);
+        }
+        let mut carry = 0;
+        for (i, digit) in self.0.iter_mut().enumerate() {
+            let sum = *digit as u64 + rhs.0.get(i).copied().unwrap_or(0) as u64 + carry;
+            *digit = sum as u32;
+            carry = sum >> 32;
+            if carry == 0 && i >= rhs.0.len() {
+                break;
+            }
+        }
+        if carry > 0 {
+            self.0.push(carry as u32);
+        }
+    }
+}
+
+impl Add<&BigUint> for BigUint {
+    type Output = BigUint;
+
+    fn add(mut self, rhs: &BigUint) -> BigUint {
+        self += rhs;
+        self
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(n) = self.to_u128() {
+            return write!(f, "{}", n);
+        }
+        // Split into base 10^9 chunks, by long division
+        const CHUNK: u64 = 1_000_000_000;
+        let mut digits = self.0.clone();
+        let mut chunks = vec![];
+        while !digits.is_empty() {
+            let mut rem = 0;
+            for digit in digits.iter_mut().rev() {
+                let cur = rem << 32 | *digit as u64;
+                *digit = (cur / CHUNK) as u32;
+                rem = cur % CHUNK;
+            }
+            while digits.last() == Some(&0) {
+                digits.pop();
+            }
+            chunks.push(rem);
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        chunks.try_for_each(|chunk| write!(f, "{:09}", chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addition() {
+        let mut n = BigUint::from(u64::MAX);
+        n += &BigUint::from(1);
+        assert_eq!(n.to_u128(), Some(1 << 64));
+        assert!(BigUint::zero().is_zero());
+        assert_eq!(BigUint::zero().to_string(), "0");
+
+        // Fibonacci numbers, past u128
+        let (mut a, mut b) = (BigUint::zero(), BigUint::from(1));
+        for _ in 0..200 {
+            let next = a + &b;
+            a = b;
+            b = next;
+        }
+        assert_eq!(a.to_u128(), None);
+        assert_eq!(a.to_string(), "280571172992510140037611932413038677189525");
+    }
+}
@@ -0,0 +1,134 @@
+//! A small REPL around `assembly::Debugger`.
+//!
+//! Usage: `cargo run --bin debugger -- <program file>`
+
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    process,
+};
+
+use aoc_2020::assembly::{Breakpoint, Debugger, Machine, Operand, Program, Stop, REGISTERS};
+
+const HELP: &str = "Commands:
+  step [n]            run n instructions (default 1)
+  continue            run until a breakpoint, a watchpoint, the end or a loop
+  restart             go back to the start of the program, clearing the trace
+  print               show the instruction pointer, the registers and the next instruction
+  break <idx|opcode>  add a breakpoint
+  delete <idx|opcode> remove a breakpoint
+  watch               toggle the accumulator watchpoint
+  trace [file]        dump the trace as JSON lines (to stdout by default)
+  quit";
+
+fn parse_breakpoint(program: &Program, arg: Option<&str>) -> Option<Breakpoint> {
+    let arg = arg?;
+    match arg.parse() {
+        Ok(idx) => Some(Breakpoint::Index(idx)),
+        Err(_) => program
+            .instruction_set()
+            .lookup(arg)
+            .map(|(op, _)| Breakpoint::Opcode(op)),
+    }
+}
+
+fn print_state(machine: &Machine) {
+    println!("ip = {}", machine.instruction_ptr());
+    let registers = machine.registers();
+    let values: Vec<String> = (0..REGISTERS)
+        .map(Operand::Register)
+        .filter(|&r| r == Operand::Register(0) || registers.get(r) != 0)
+        .map(|r| format!("{} = {}", r, registers.get(r)))
+        .collect();
+    println!("{}", values.join(", "));
+    match machine.instruction(machine.instruction_ptr()) {
+        Some(inst) => println!(
+            "next: {}",
+            machine.program().instruction_set().format(&inst)
+        ),
+        None => println!("terminated"),
+    }
+}
+
+fn print_stop(stop: Stop) {
+    match stop {
+        Stop::Step => {}
+        Stop::Breakpoint(idx) => println!("breakpoint at {}", idx),
+        Stop::Watchpoint { old, new } => println!("acc changed: {} -> {}", old, new),
+        Stop::Loop(idx) => println!("infinite loop at {}", idx),
+        Stop::Terminated => println!("terminated"),
+    }
+}
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("Usage: debugger <program file>");
+        process::exit(2);
+    });
+    let source = fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("Couldn't read {}: {}", path, e);
+        process::exit(1);
+    });
+    let program = Program::from_input(source.trim_end(), 0).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let mut debugger = Debugger::new(&program);
+    let mut watch = false;
+    let stdin = io::stdin();
+    print!("> ");
+    io::stdout().flush().unwrap();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("step") | Some("s") => {
+                let count = parts.next().and_then(|n| n.parse().ok()).unwrap_or(1);
+                for _ in 0..count {
+                    let stop = debugger.step();
+                    if stop != Stop::Step {
+                        print_stop(stop);
+                        break;
+                    }
+                }
+                print_state(debugger.machine());
+            }
+            Some("continue") | Some("c") => {
+                print_stop(debugger.resume());
+                print_state(debugger.machine());
+            }
+            Some("restart") | Some("r") => {
+                debugger.restart();
+                print_state(debugger.machine());
+            }
+            Some("print") | Some("p") => print_state(debugger.machine()),
+            Some("break") | Some("b") => match parse_breakpoint(&program, parts.next()) {
+                Some(bp) => debugger.add_breakpoint(bp),
+                None => println!("expected an instruction index or an opcode"),
+            },
+            Some("delete") | Some("d") => match parse_breakpoint(&program, parts.next()) {
+                Some(bp) if debugger.remove_breakpoint(bp) => {}
+                _ => println!("no such breakpoint"),
+            },
+            Some("watch") | Some("w") => {
+                watch = !watch;
+                debugger.watch_accumulator(watch);
+                println!("watching acc: {}", watch);
+            }
+            Some("trace") | Some("t") => {
+                let res = match parts.next() {
+                    Some(file) => fs::File::create(file).and_then(|f| debugger.write_trace(f)),
+                    None => debugger.write_trace(io::stdout()),
+                };
+                if let Err(e) = res {
+                    println!("couldn't write the trace: {}", e);
+                }
+            }
+            Some("quit") | Some("q") => break,
+            Some(_) => println!("{}", HELP),
+            None => {}
+        }
+        print!("> ");
+        io::stdout().flush().unwrap();
+    }
+}
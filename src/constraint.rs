@@ -0,0 +1,268 @@
+//! Assigns a distinct value to every key, given the values each key can take.
+//!
+//! Used by day 16 (fields to columns) and day 21 (allergens to ingredients).
+//! The solver propagates naked singles (a key with a single possible value) and,
+//! when there are as many values as keys, hidden singles (a value only one key can take).
+//! Whatever is left is settled by backtracking.
+
+use std::{error::Error, fmt, hash::Hash};
+
+use fxhash::FxHashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError<K> {
+    /// No assignment gives every key a different value.
+    /// `key` is the key that ran out of values, if propagation alone could tell.
+    Unsatisfiable { key: Option<K> },
+    /// More than one assignment works, and `key` has a different value in two of them
+    Ambiguous { key: K },
+}
+
+impl<K: fmt::Debug> fmt::Display for SolveError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Unsatisfiable { key: Some(key) } => {
+                write!(f, "no value left for {:?}", key)
+            }
+            SolveError::Unsatisfiable { key: None } => {
+                write!(f, "the keys can't all have different values")
+            }
+            SolveError::Ambiguous { key } => write!(f, "{:?} can take more than one value", key),
+        }
+    }
+}
+
+impl<K: fmt::Debug> Error for SolveError<K> {}
+
+/// The search state, with keys and values replaced by their indices
+#[derive(Clone)]
+struct State {
+    /// `domains[k][v]`: whether key k can still take value v
+    domains: Vec<Vec<bool>>,
+    /// How many values each key can still take
+    sizes: Vec<usize>,
+    /// How many keys can still take each value
+    counts: Vec<usize>,
+    assigned: Vec<Option<usize>>,
+    taken: Vec<bool>,
+    /// Every value must be used, so hidden singles apply
+    exact: bool,
+    key_queue: Vec<usize>,
+    value_queue: Vec<usize>,
+}
+
+/// The key that ran out of values, if any
+type Conflict = Option<usize>;
+
+impl State {
+    fn remove(&mut self, key: usize, value: usize) -> Result<(), Conflict> {
+        if !self.domains[key][value] {
+            return Ok(());
+        }
+        self.domains[key][value] = false;
+        self.sizes[key] -= 1;
+        self.counts[value] -= 1;
+        match self.sizes[key] {
+            0 => return Err(Some(key)),
+            1 => self.key_queue.push(key),
+            _ => {}
+        }
+        if self.exact && !self.taken[value] {
+            match self.counts[value] {
+                0 => return Err(None),
+                1 => self.value_queue.push(value),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, key: usize, value: usize) -> Result<(), Conflict> {
+        self.assigned[key] = Some(value);
+        self.taken[value] = true;
+        for other in 0..self.counts.len() {
+            if other != value {
+                self.remove(key, other)?;
+            }
+        }
+        for other in 0..self.sizes.len() {
+            if other != key {
+                self.remove(other, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn propagate(&mut self) -> Result<(), Conflict> {
+        loop {
+            if let Some(key) = self.key_queue.pop() {
+                if self.assigned[key].is_none() {
+                    let value = self.domains[key].iter().position(|&d| d).unwrap();
+                    self.assign(key, value)?;
+                }
+            } else if let Some(value) = self.value_queue.pop() {
+                if !self.taken[value] && self.counts[value] == 1 {
+                    let key = (0..self.sizes.len())
+                        .find(|&k| self.domains[k][value])
+                        .unwrap();
+                    self.assign(key, value)?;
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Collects up to 2 complete assignments
+    fn search(self, solutions: &mut Vec<Vec<usize>>) {
+        let key = (0..self.sizes.len())
+            .filter(|&k| self.assigned[k].is_none())
+            .min_by_key(|&k| self.sizes[k]);
+        let key = match key {
+            Some(key) => key,
+            None => {
+                solutions.push(self.assigned.iter().map(|v| v.unwrap()).collect());
+                return;
+            }
+        };
+        for value in (0..self.counts.len()).filter(|&v| self.domains[key][v]) {
+            if solutions.len() >= 2 {
+                return;
+            }
+            let mut state = self.clone();
+            if state
+                .assign(key, value)
+                .and_then(|_| state.propagate())
+                .is_ok()
+            {
+                state.search(solutions);
+            }
+        }
+    }
+}
+
+/// Gives every key one of its possible values, so that no two keys share a value.
+/// The assignment must be unique; the result is in the same order as the input.
+pub fn solve<K, V>(input: &[(K, Vec<V>)]) -> Result<Vec<(K, V)>, SolveError<K>>
+where
+    K: Clone,
+    V: Copy + Eq + Hash,
+{
+    let mut values = vec![];
+    let mut indices = FxHashMap::default();
+    for &v in input.iter().flat_map(|(_, vs)| vs) {
+        indices.entry(v).or_insert_with(|| {
+            values.push(v);
+            values.len() - 1
+        });
+    }
+    let mut state = State {
+        domains: vec![vec![false; values.len()]; input.len()],
+        sizes: vec![0; input.len()],
+        counts: vec![0; values.len()],
+        assigned: vec![None; input.len()],
+        taken: vec![false; values.len()],
+        exact: values.len() == input.len(),
+        key_queue: vec![],
+        value_queue: vec![],
+    };
+    for (k, (_, vs)) in input.iter().enumerate() {
+        for v in vs {
+            let v = indices[v];
+            if !state.domains[k][v] {
+                state.domains[k][v] = true;
+                state.sizes[k] += 1;
+                state.counts[v] += 1;
+            }
+        }
+    }
+    let unsatisfiable = |conflict: Conflict| SolveError::Unsatisfiable {
+        key: conflict.map(|k| input[k].0.clone()),
+    };
+    for k in 0..input.len() {
+        match state.sizes[k] {
+            0 => return Err(unsatisfiable(Some(k))),
+            1 => state.key_queue.push(k),
+            _ => {}
+        }
+    }
+    if state.exact {
+        state.value_queue = (0..values.len())
+            .filter(|&v| state.counts[v] == 1)
+            .collect();
+    }
+    state.propagate().map_err(unsatisfiable)?;
+    let mut solutions = vec![];
+    state.search(&mut solutions);
+    match solutions.as_slice() {
+        [] => Err(unsatisfiable(None)),
+        [solution] => Ok(input
+            .iter()
+            .zip(solution)
+            .map(|((k, _), &v)| (k.clone(), values[v]))
+            .collect()),
+        [a, b, ..] => {
+            let k = (0..input.len()).find(|&k| a[k] != b[k]).unwrap();
+            Err(SolveError::Ambiguous {
+                key: input[k].0.clone(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn singles() {
+        // Day 16's second example
+        let input = vec![
+            ("class", vec![1, 2]),
+            ("row", vec![0, 1, 2]),
+            ("seat", vec![2]),
+        ];
+        assert_eq!(
+            solve(&input),
+            Ok(vec![("class", 1), ("row", 0), ("seat", 2)])
+        );
+    }
+
+    #[test]
+    fn ambiguous() {
+        // d has to be 3, but a, b and c can rotate their values
+        let input = vec![
+            ("a", vec![0, 1]),
+            ("b", vec![1, 2]),
+            ("c", vec![2, 0]),
+            ("d", vec![0, 3]),
+        ];
+        assert_eq!(solve(&input), Err(SolveError::Ambiguous { key: "a" }));
+        let input = vec![("a", vec![0, 1, 2]), ("b", vec![1, 2]), ("c", vec![1, 3])];
+        assert_eq!(solve(&input), Err(SolveError::Ambiguous { key: "a" }));
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        let input = vec![("a", vec![0]), ("b", vec![0]), ("c", vec![1, 2])];
+        assert_eq!(
+            solve(&input),
+            Err(SolveError::Unsatisfiable { key: Some("a") })
+        );
+        let input = vec![("a", vec![0, 1]), ("b", vec![0, 1]), ("c", vec![0, 1])];
+        assert_eq!(solve(&input), Err(SolveError::Unsatisfiable { key: None }));
+        assert_eq!(
+            solve::<&str, u8>(&[("a", vec![])]).unwrap_err().to_string(),
+            "no value left for \"a\""
+        );
+    }
+
+    #[test]
+    fn large() {
+        // Key i can take any value from i to n - 1, so it has to take i
+        let n = 1000;
+        let input: Vec<(usize, Vec<usize>)> = (0..n).map(|i| (i, (i..n).collect())).collect();
+        let res = solve(&input).unwrap();
+        assert!(res.iter().all(|&(k, v)| k == v));
+    }
+}
@@ -1,65 +1,140 @@
-struct Combinations {
-    /// The numbers >= 1000
-    high: Vec<i32>,
-    /// The other numbers
-    low: Vec<i32>,
-}
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
 
-fn parse_numbers(input: &str) -> Combinations {
-    let mut high = vec![];
-    let mut low = vec![];
+fn parse_all(input: &str) -> ParseResult<Vec<i64>> {
+    let src = Source::new(1, input);
+    let mut vec = vec![];
     for line in input.lines() {
         if line.is_empty() {
             continue;
         }
-        let num = line.parse().unwrap();
-        (if num >= 1000 { &mut high } else { &mut low }).push(num);
+        vec.push(src.parse(line)?);
     }
-    Combinations { high, low }
+    Ok(vec)
 }
 
-fn parse_all(input: &str) -> Vec<i32> {
-    let mut vec = vec![];
-    for line in input.lines() {
-        if line.is_empty() {
-            continue;
+/// Looks for `k` entries of `sorted` that sum to `target`, pushing each combination to `found`
+/// (prefixed by the entries picked so far) until there are `limit` of them.
+/// Equal entries are only tried once in each position, so every combination is found once.
+/// Sums are worked out in `i128`, so entries anywhere in the `i64` range can't overflow them.
+fn k_sum(
+    sorted: &[i64],
+    k: usize,
+    target: i128,
+    picked: &mut Vec<i64>,
+    found: &mut Vec<Vec<i64>>,
+    limit: usize,
+) {
+    if found.len() >= limit || sorted.len() < k {
+        return;
+    }
+    match k {
+        0 => {
+            if target == 0 {
+                found.push(picked.clone());
+            }
+        }
+        // Two pointers, moving inwards from both ends
+        2 => {
+            let (mut lo, mut hi) = (0, sorted.len() - 1);
+            while lo < hi && found.len() < limit {
+                let sum = sorted[lo] as i128 + sorted[hi] as i128;
+                if sum < target {
+                    lo += 1;
+                } else if sum > target {
+                    hi -= 1;
+                } else {
+                    let mut combination = picked.clone();
+                    combination.extend_from_slice(&[sorted[lo], sorted[hi]]);
+                    found.push(combination);
+                    let (a, b) = (sorted[lo], sorted[hi]);
+                    while lo < hi && sorted[lo] == a {
+                        lo += 1;
+                    }
+                    while lo < hi && sorted[hi] == b {
+                        hi -= 1;
+                    }
+                }
+            }
+        }
+        _ => {
+            for i in 0..=sorted.len() - k {
+                if i > 0 && sorted[i] == sorted[i - 1] {
+                    continue;
+                }
+                // The smallest and largest sums starting with sorted[i]
+                let sum = |entries: &[i64]| entries.iter().map(|&e| e as i128).sum::<i128>();
+                let smallest = sum(&sorted[i..i + k]);
+                let largest = sorted[i] as i128 + sum(&sorted[sorted.len() - (k - 1)..]);
+                if smallest > target {
+                    break;
+                }
+                if largest < target {
+                    continue;
+                }
+                picked.push(sorted[i]);
+                k_sum(
+                    &sorted[i + 1..],
+                    k - 1,
+                    target - sorted[i] as i128,
+                    picked,
+                    found,
+                    limit,
+                );
+                picked.pop();
+            }
         }
-        let num = line.parse().unwrap();
-        vec.push(num);
     }
-    vec
+}
+
+/// Finds `k` entries (at different positions) that sum to `target`, in ascending order
+pub fn find_k_sum(nums: &[i64], k: usize, target: i64) -> Option<Vec<i64>> {
+    let mut sorted = nums.to_vec();
+    sorted.sort_unstable();
+    let mut found = vec![];
+    k_sum(&sorted, k, target.into(), &mut vec![], &mut found, 1);
+    found.pop()
+}
+
+/// Every combination of `k` entries that sum to `target`, each in ascending order.
+/// Equal entries don't make distinct combinations.
+pub fn find_all_k_sums(nums: &[i64], k: usize, target: i64) -> Vec<Vec<i64>> {
+    let mut sorted = nums.to_vec();
+    sorted.sort_unstable();
+    let mut found = vec![];
+    k_sum(
+        &sorted,
+        k,
+        target.into(),
+        &mut vec![],
+        &mut found,
+        usize::MAX,
+    );
+    found
+}
+
+/// There's no answer if the product doesn't fit in an `i128` either
+fn product_of_k_sum(input: &str, k: usize) -> ParseResult<Answer> {
+    let nums = parse_all(input)?;
+    Ok(find_k_sum(&nums, k, 2020)
+        .and_then(|entries| {
+            entries
+                .iter()
+                .try_fold(1i128, |product, &e| product.checked_mul(e.into()))
+        })
+        .into())
 }
 
 #[aoc(day1, part1)]
-pub fn part1(input: &str) -> i32 {
-    let combos = parse_numbers(input);
-    for high in &combos.high {
-        for low in &combos.low {
-            if low + high == 2020 {
-                return low * high;
-            }
-        }
-    }
-    0
+pub fn part1(input: &str) -> Result<Answer, ParseError> {
+    product_of_k_sum(input, 2)
 }
 
 #[aoc(day1, part2)]
-pub fn part2(input: &str) -> i32 {
-    let nums = {
-        let mut nums = parse_all(input);
-        nums.sort();
-        nums
-    };
-    for num1 in &nums {
-        for num2 in &nums {
-            let sum = 2020 - (num1 + num2);
-            if nums.binary_search(&sum).is_ok() {
-                // https://www.wolframalpha.com/input/?i=xyz%3B+x%2By%2Bz%3D2020
-                return -num1 * num2 * (num1 + num2 - 2020);
-            }
-        }
-    }
-    0
+pub fn part2(input: &str) -> Result<Answer, ParseError> {
+    product_of_k_sum(input, 3)
 }
 
 #[cfg(test)]
@@ -69,12 +144,80 @@ mod tests {
     #[test]
     fn part1_example() {
         let input = example!(1721 979 366 299 675 1456);
-        assert_eq!(part1(input), 514579);
+        assert_eq!(part1(input), Ok(Answer::Number(514579)));
     }
 
     #[test]
     fn part2_example() {
         let input = example!(1721 979 366 299 675 1456);
-        assert_eq!(part2(input), 241861950);
+        assert_eq!(part2(input), Ok(Answer::Number(241861950)));
+    }
+
+    #[test]
+    fn no_solution() {
+        let input = example!(1 2 3);
+        assert_eq!(part1(input), Ok(Answer::NoSolution));
+        assert_eq!(part2(input), Ok(Answer::NoSolution));
+    }
+
+    #[test]
+    fn large_entries() {
+        let (max, min) = (i64::MAX, i64::MIN);
+        // The three entries summing to 2020 have a product too big for an i128
+        let input = format!("{}\n{}\n{}\n{}\n2021", max, max - 1, min, 2020 - max);
+        assert_eq!(
+            part1(&input),
+            Ok(Answer::Number(max as i128 * (2020 - max) as i128))
+        );
+        assert_eq!(part2(&input), Ok(Answer::NoSolution));
+        assert_eq!(
+            find_k_sum(&[max, max, -max, min], 2, 0),
+            Some(vec![-max, max])
+        );
+        assert_eq!(find_k_sum(&[max, max, 1], 3, 0), None);
+        assert_eq!(
+            find_k_sum(&[max, max, min + 1, min], 3, max - 1),
+            Some(vec![min, max, max])
+        );
+        assert_eq!(
+            find_k_sum(&[max, max - 1, min, 5], 3, min + 5 + (max - 1)),
+            Some(vec![min, 5, max - 1])
+        );
+    }
+
+    #[test]
+    fn k_sums() {
+        let nums = [1721, 979, 366, 299, 675, 1456];
+        assert_eq!(find_k_sum(&nums, 2, 2020), Some(vec![299, 1721]));
+        assert_eq!(find_k_sum(&nums, 3, 2020), Some(vec![366, 675, 979]));
+        assert_eq!(
+            find_k_sum(&nums, 4, 299 + 366 + 675 + 1456),
+            Some(vec![299, 366, 675, 1456])
+        );
+        assert_eq!(find_k_sum(&nums, 2, 1721 * 2), None);
+        assert_eq!(find_k_sum(&nums, 7, 0), None);
+        // Each entry can only be used once, but equal entries are fine
+        assert_eq!(find_k_sum(&[1010, 1], 2, 2020), None);
+        assert_eq!(
+            find_k_sum(&[1010, 1, 1010], 2, 2020),
+            Some(vec![1010, 1010])
+        );
+        assert_eq!(find_k_sum(&[-5, 3, 10], 2, 5), Some(vec![-5, 10]));
+    }
+
+    #[test]
+    fn all_k_sums() {
+        let nums = [1, 2, 3, 4, 5, 5];
+        assert_eq!(find_all_k_sums(&nums, 2, 6), vec![vec![1, 5], vec![2, 4]]);
+        assert_eq!(
+            find_all_k_sums(&nums, 3, 10),
+            vec![vec![1, 4, 5], vec![2, 3, 5]]
+        );
+        assert_eq!(
+            find_all_k_sums(&nums, 4, 15),
+            vec![vec![1, 4, 5, 5], vec![2, 3, 5, 5]]
+        );
+        assert_eq!(find_all_k_sums(&nums, 1, 5), vec![vec![5]]);
+        assert_eq!(find_all_k_sums(&nums, 0, 0), vec![Vec::<i64>::new()]);
     }
 }
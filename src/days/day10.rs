@@ -1,54 +1,192 @@
-fn parse_input(input: &str) -> Vec<u8> {
-    input.lines().map(|l| l.parse().unwrap()).collect()
+//! Chaining joltage adapters: each adapter takes an input between 1 and `max_step` jolts lower than its output.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    bignum::BigUint,
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
+const MAX_STEP: u64 = 3;
+
+fn parse_input(input: &str) -> ParseResult<JoltageChain> {
+    let src = Source::new(10, input);
+    let lines: Vec<&str> = input.lines().collect();
+    let adapters = lines
+        .iter()
+        .map(|l| src.parse(l))
+        .collect::<ParseResult<Vec<u64>>>()?;
+    JoltageChain::new(&adapters, MAX_STEP).ok_or_else(|| {
+        // The device's own adapter must also fit
+        let highest = (0..lines.len()).max_by_key(|&i| adapters[i]).unwrap();
+        src.error(lines[highest], "joltage too high")
+    })
+}
+
+/// The outlet (0 jolts), the adapters in order, then the device (`max_step` above the highest adapter)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoltageChain {
+    max_step: u64,
+    joltages: Vec<u64>,
 }
 
-/// Easy sorted array
-fn specialize(input: &[u8]) -> [Option<u8>; std::u8::MAX as usize] {
-    let mut arr = [None; std::u8::MAX as usize];
-    for i in input {
-        arr[*i as usize] = Some(*i);
+impl JoltageChain {
+    /// `None` if `max_step` is 0, or if the device's joltage doesn't fit in a `u64`
+    pub fn new(adapters: &[u64], max_step: u64) -> Option<JoltageChain> {
+        if max_step == 0 {
+            return None;
+        }
+        let mut joltages = Vec::with_capacity(adapters.len() + 2);
+        joltages.push(0);
+        joltages.extend_from_slice(adapters);
+        joltages.sort_unstable();
+        let device = joltages[joltages.len() - 1].checked_add(max_step)?;
+        joltages.push(device);
+        Some(JoltageChain { max_step, joltages })
+    }
+
+    pub fn device(&self) -> u64 {
+        *self.joltages.last().unwrap()
+    }
+
+    /// How many times each difference comes up when every adapter is used
+    pub fn histogram(&self) -> BTreeMap<u64, usize> {
+        let mut histogram = BTreeMap::new();
+        for pair in self.joltages.windows(2) {
+            *histogram.entry(pair[1] - pair[0]).or_default() += 1;
+        }
+        histogram
+    }
+
+    /// Whether every adapter can be used at once
+    pub fn uses_all(&self) -> bool {
+        self.histogram()
+            .keys()
+            .all(|&diff| diff >= 1 && diff <= self.max_step)
+    }
+
+    /// The indices of the joltages that can come after the one at `i`
+    fn next(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let from = self.joltages[i];
+        (i + 1..self.joltages.len())
+            .take_while(move |&j| self.joltages[j] - from <= self.max_step)
+            .filter(move |&j| self.joltages[j] > from)
+    }
+
+    /// The number of ways to connect the outlet to the device, from each joltage
+    fn ways(&self) -> Vec<BigUint> {
+        let n = self.joltages.len();
+        let mut ways = vec![BigUint::zero(); n];
+        ways[n - 1] = BigUint::from(1);
+        for i in (0..n - 1).rev() {
+            let mut count = BigUint::zero();
+            for j in self.next(i) {
+                count += &ways[j];
+            }
+            ways[i] = count;
+        }
+        ways
+    }
+
+    /// The number of ways to connect the outlet to the device, with any of the adapters
+    pub fn arrangements(&self) -> BigUint {
+        self.ways().swap_remove(0)
+    }
+
+    /// Every arrangement (the joltages of the adapters used), computed as they are needed
+    pub fn iter_arrangements(&self) -> Arrangements<'_> {
+        let dead_ends = self.ways().iter().map(BigUint::is_zero).collect();
+        Arrangements {
+            chain: self,
+            dead_ends,
+            path: vec![],
+            done: false,
+        }
     }
-    arr
 }
 
-#[aoc(day10, part1)]
-fn part1(input: &str) -> i32 {
-    let nums = specialize(&parse_input(input));
-    let mut diffs = (0, 0); // 1-diff, 3-diff
-    let mut idx = 0; // Index, which is also the current joltage
-    loop {
-        if let Some(j) = nums.get(idx + 1).map(|&x| x).flatten() {
-            diffs.0 += 1;
-            idx = j as usize;
-        } else if let Some(j) = nums.get(idx + 2).map(|&x| x).flatten() {
-            idx = j as usize;
-        } else if let Some(j) = nums.get(idx + 3).map(|&x| x).flatten() {
-            diffs.1 += 1;
-            idx = j as usize;
+/// The arrangements of a [`JoltageChain`], smallest adapters first
+#[derive(Debug, Clone)]
+pub struct Arrangements<'a> {
+    chain: &'a JoltageChain,
+    /// The joltages that can't reach the device
+    dead_ends: Vec<bool>,
+    /// The indices of the joltages of the last arrangement
+    path: Vec<usize>,
+    done: bool,
+}
+
+impl Arrangements<'_> {
+    /// Picks the first possible joltage until the device
+    fn complete(&mut self) {
+        let end = self.chain.joltages.len() - 1;
+        while let Some(&last) = self.path.last().filter(|&&last| last != end) {
+            let next = self.chain.next(last).find(|&j| !self.dead_ends[j]).unwrap();
+            self.path.push(next);
+        }
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<u64>;
+
+    fn next(&mut self) -> Option<Vec<u64>> {
+        if self.done {
+            return None;
+        }
+        if self.path.is_empty() {
+            if self.dead_ends[0] {
+                self.done = true;
+                return None;
+            }
+            self.path.push(0);
         } else {
-            diffs.1 += 1; // Last one is always a 3-diff
-            break;
+            // Go back to the last joltage with another way forward
+            loop {
+                let last = self.path.pop().unwrap();
+                let prev = match self.path.last() {
+                    Some(&prev) => prev,
+                    None => {
+                        self.done = true;
+                        return None;
+                    }
+                };
+                let next = self
+                    .chain
+                    .next(prev)
+                    .find(|&j| j > last && !self.dead_ends[j]);
+                if let Some(next) = next {
+                    self.path.push(next);
+                    break;
+                }
+            }
         }
+        self.complete();
+        let adapters = &self.path[1..self.path.len() - 1];
+        Some(adapters.iter().map(|&i| self.chain.joltages[i]).collect())
     }
-    diffs.0 * diffs.1
 }
 
-#[aoc(day10, part2)]
-fn part2(input: &str) -> usize {
-    let (nums, max) = {
-        let mut nums = parse_input(input);
-        nums.sort();
-        let max = *nums.last().unwrap() as usize + 3;
-        nums.push(max as u8);
-        (nums, max)
-    };
-    let mut arrangements = vec![0; max + 1];
-    arrangements[0] = 1;
-    for num in nums {
-        let num = num as usize;
-        arrangements[num] = arrangements[num.saturating_sub(3)..num].iter().sum();
+#[aoc(day10, part1)]
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let chain = parse_input(input)?;
+    if !chain.uses_all() {
+        return Ok(Answer::NoSolution);
     }
-    *arrangements.last().unwrap()
+    let histogram = chain.histogram();
+    let count = |diff| histogram.get(&diff).copied().unwrap_or(0);
+    Ok((count(1) * count(3)).into())
+}
+
+#[aoc(day10, part2)]
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let chain = parse_input(input)?;
+    let arrangements = chain.arrangements();
+    Ok(match arrangements.to_u128() {
+        Some(n) if n <= i128::MAX as u128 => Answer::Number(n as i128),
+        _ => Answer::Text(arrangements.to_string()),
+    })
 }
 
 #[cfg(test)]
@@ -58,12 +196,70 @@ mod tests {
     #[test]
     fn part1_example() {
         let input = example!(28 33 18 42 31 14 46 20 48 47 24 23 49 45 19 38 39 11 1 32 25 35 8 17 7 9 4 2 34 10 3);
-        assert_eq!(part1(input), 22 * 10);
+        assert_eq!(part1(input), Ok(Answer::Number(22 * 10)));
     }
 
     #[test]
     fn part2_example() {
         let input = example!(16 10 15 5 1 11 7 19 6 12 4);
-        assert_eq!(part2(input), 8);
+        assert_eq!(part2(input), Ok(Answer::Number(8)));
+    }
+
+    #[test]
+    fn chains() {
+        let chain = JoltageChain::new(&[16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4], 3).unwrap();
+        assert_eq!(chain.device(), 22);
+        let histogram: Vec<(u64, usize)> = chain.histogram().into_iter().collect();
+        assert_eq!(histogram, vec![(1, 7), (3, 5)]);
+        let arrangements: Vec<Vec<u64>> = chain.iter_arrangements().collect();
+        assert_eq!(arrangements.len(), 8);
+        assert_eq!(arrangements[0], vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19]);
+        assert_eq!(arrangements[7], vec![1, 4, 7, 10, 12, 15, 16, 19]);
+
+        let chain = JoltageChain::new(&[1_000_000_000_000, 1_000_000_000_001], 5).unwrap();
+        assert!(!chain.uses_all());
+        assert!(chain.arrangements().is_zero());
+        assert_eq!(chain.iter_arrangements().next(), None);
+
+        let chain = JoltageChain::new(&[10, 20, 20, 30], 10).unwrap();
+        assert_eq!(chain.histogram().get(&0), Some(&1));
+        assert!(!chain.uses_all());
+        assert_eq!(chain.arrangements().to_u128(), Some(2));
+        assert_eq!(
+            JoltageChain::new(&[], 1)
+                .unwrap()
+                .iter_arrangements()
+                .count(),
+            1
+        );
+        assert_eq!(JoltageChain::new(&[1], 0), None);
+        assert_eq!(JoltageChain::new(&[u64::MAX - 2], 3), None);
+        let err = part1("1\n18446744073709551613\n2").unwrap_err();
+        assert_eq!((err.line, err.reason.as_str()), (2, "joltage too high"));
+    }
+
+    #[test]
+    fn huge_counts() {
+        // Any of the adapters below 200 can be left out
+        let adapters: Vec<u64> = (1..=200).collect();
+        let chain = JoltageChain::new(&adapters, 200).unwrap();
+        assert_eq!(
+            chain.arrangements().to_string(),
+            "803469022129495137770981046170581301261101496891396417650688"
+        );
+        let second: Vec<u64> = (1..=198).chain(Some(200)).collect();
+        assert_eq!(chain.iter_arrangements().nth(1), Some(second));
+
+        let input = adapters
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            part2(&input),
+            Ok(Answer::from(
+                "52622583840983769603765180599790256716084480555530641"
+            ))
+        );
     }
 }
@@ -1,263 +1,159 @@
-#[derive(Clone)]
-struct Grid {
-    seats: Vec<Vec<Option<Seat>>>,
-    max_x: usize,
-}
+//! People filling a waiting area: seats change all at once, depending on how many of the neighbouring seats are taken.
 
-struct GridIter<'a> {
-    grid: &'a mut Grid,
-    snapshot: Grid,
-    point: (usize, usize),
-}
+use std::mem;
 
-#[derive(Clone, Copy, Debug)]
-struct Seat {
-    vacancy: SeatVacancy,
-}
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    grid::Grid,
+    life::Seats,
+    solver::Answer,
+};
 
 #[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Debug)]
-enum SeatVacancy {
+pub enum SeatVacancy {
     Empty,
     Occupied,
 }
 
-type Item<'a> = (
-    &'a mut Option<Seat>,
-    &'a Option<Seat>,
-    [Option<&'a Seat>; 8],
-);
-impl<'a> GridIter<'a> {
-    fn next(&mut self) -> Option<Item<'_>> {
-        let current = self
-            .snapshot
-            .seats
-            .get(self.point.1)
-            .and_then(|v| v.get(self.point.0));
-        let x = self.point.0;
-        let y = self.point.1;
-        self.point.0 += 1;
-        if self.point.0 >= self.snapshot.max_x {
-            self.point.0 = 0;
-            self.point.1 += 1;
-        }
-        if current.is_none() {
-            return None;
-        }
-        let mutable = self.grid.seats.get_mut(y).and_then(|v| v.get_mut(x));
-        let mut adjacent = [None; 8];
-        let mut i = 0;
-        for y1 in -1i32..=1 {
-            for x1 in -1i32..=1 {
-                if x1 == 0 && y1 == 0 {
-                    continue;
-                }
-                let x = {
-                    if x1 < 0 && x == 0 || x1 > 0 && x == self.snapshot.max_x - 1 {
-                        None
-                    } else {
-                        let x = x as i32 + x1;
-                        Some(x as usize)
-                    }
-                };
-                if let Some(x) = x {
-                    adjacent[i] = self
-                        .snapshot
-                        .seats
-                        .get((y as i32 + y1) as usize)
-                        .and_then(|v| v.get(x).and_then(|o| o.as_ref()));
-                }
-                i += 1;
-            }
+/// `None` is floor
+pub type WaitingArea = Grid<Option<SeatVacancy>>;
+
+pub fn parse_input(input: &str) -> ParseResult<WaitingArea> {
+    Grid::parse(
+        &Source::new(11, input),
+        input,
+        "`.`, `L` or `#`",
+        |c| match c {
+            '.' => Some(None),
+            'L' => Some(Some(SeatVacancy::Empty)),
+            '#' => Some(Some(SeatVacancy::Occupied)),
+            _ => None,
+        },
+    )
+}
+
+/// How many occupied neighbours people put up with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tolerance {
+    /// An empty seat fills up if at most this many neighbours are occupied
+    pub fill: usize,
+    /// An occupied seat empties out if at least this many neighbours are occupied
+    pub leave: usize,
+}
+
+impl Tolerance {
+    /// Part 1, looking at the adjacent seats
+    pub const NEAR: Tolerance = Tolerance { fill: 0, leave: 4 };
+    /// Part 2, looking at the first seat in each direction
+    pub const FAR: Tolerance = Tolerance { fill: 0, leave: 5 };
+}
+
+/// Runs the waiting area a generation at a time, reading one buffer of seats and writing the other
+#[derive(Debug, Clone)]
+pub struct SeatSimulator {
+    seats: Seats,
+    tolerance: Tolerance,
+    occupied: Vec<bool>,
+    next: Vec<bool>,
+    generation: usize,
+}
+
+impl SeatSimulator {
+    /// Each seat's neighbours are the seats right next to it, or the first seat in each of the 8 directions if `far`
+    pub fn new(area: &WaitingArea, far: bool, tolerance: Tolerance) -> SeatSimulator {
+        let seats = Seats::new(&area.map(Option::is_some), far);
+        let occupied: Vec<bool> = (0..seats.len())
+            .map(|seat| area[seats.position(seat)] == Some(SeatVacancy::Occupied))
+            .collect();
+        SeatSimulator {
+            next: occupied.clone(),
+            seats,
+            tolerance,
+            occupied,
+            generation: 0,
         }
-        Some((mutable.unwrap(), current.unwrap(), adjacent))
     }
 
-    fn next_any(&mut self) -> Option<Item<'_>> {
-        let current = self
-            .snapshot
-            .seats
-            .get(self.point.1)
-            .and_then(|v| v.get(self.point.0));
-        let x = self.point.0;
-        let y = self.point.1;
-        self.point.0 += 1;
-        if self.point.0 >= self.snapshot.max_x {
-            self.point.0 = 0;
-            self.point.1 += 1;
-        }
-        if current.is_none() {
-            return None;
-        }
-        let mutable = self.grid.seats.get_mut(y).and_then(|v| v.get_mut(x));
-        let mut adjacent = [None; 8];
-        let mut i = 0;
-        for y1 in -1i32..=1 {
-            for x1 in -1i32..=1 {
-                if x1 == 0 && y1 == 0 {
-                    continue;
-                }
-                let mut tmp_pos = (x as isize, y as isize);
-                loop {
-                    let x = {
-                        if x1 < 0 && tmp_pos.0 == 0
-                            || x1 > 0 && tmp_pos.0 == (self.snapshot.max_x - 1) as isize
-                            || y1 < 0 && tmp_pos.1 == 0
-                            || y1 > 0 && tmp_pos.1 == (self.snapshot.seats.len() - 1) as isize
-                        {
-                            None
-                        } else {
-                            tmp_pos.0 += x1 as isize;
-                            tmp_pos.1 += y1 as isize;
-                            Some(tmp_pos.0)
-                        }
-                    };
-                    match x {
-                        Some(x) => {
-                            match self
-                                .snapshot
-                                .seats
-                                .get(tmp_pos.1 as usize)
-                                .and_then(|v| v.get(x as usize).and_then(|o| o.as_ref()))
-                            {
-                                Some(seat) => {
-                                    adjacent[i] = Some(seat);
-                                    break;
-                                }
-                                None => {}
-                            }
-                        }
-                        None => break,
-                    }
-                }
-                i += 1;
-            }
-        }
-        Some((mutable.unwrap(), current.unwrap(), adjacent))
+    pub fn seats(&self) -> &Seats {
+        &self.seats
     }
-}
 
-impl Grid {
-    fn parse(input: &str) -> Grid {
-        let mut src = vec![];
-        let mut max_x = 0;
-        for line in input.lines() {
-            let chars: Vec<char> = line.trim().chars().collect();
-            if max_x == 0 {
-                max_x = chars.len();
-            }
-            src.push(
-                chars
-                    .into_iter()
-                    .map(|c| match c {
-                        '.' => None,
-                        'L' => Some(Seat {
-                            vacancy: SeatVacancy::Empty,
-                        }),
-                        '#' => Some(Seat {
-                            vacancy: SeatVacancy::Occupied,
-                        }),
-                        _ => panic!("Invalid char"),
-                    })
-                    .collect(),
-            );
-        }
-        Grid { seats: src, max_x }
+    pub fn is_occupied(&self, seat: usize) -> bool {
+        self.occupied[seat]
     }
 
-    fn iter_mut(&mut self) -> GridIter<'_> {
-        GridIter {
-            snapshot: self.clone(),
-            grid: self,
-            point: (0, 0),
-        }
+    /// The number of occupied seats
+    pub fn occupied(&self) -> usize {
+        self.occupied.iter().filter(|&&o| o).count()
     }
-}
 
-#[aoc(day11, part1)]
-fn part1(input: &str) -> usize {
-    let mut grid = Grid::parse(input);
-    loop {
-        let mut iter = grid.iter_mut();
-        let mut changed = 0;
-        while let Some((mutref, shared, adjacent)) = iter.next() {
-            if mutref.is_some() {
-                match (
-                    shared,
-                    adjacent
-                        .iter()
-                        .flatten()
-                        .filter(|s| s.vacancy == SeatVacancy::Occupied)
-                        .count(),
-                ) {
-                    (Some(seat), 0) if seat.vacancy == SeatVacancy::Empty => {
-                        mutref.as_mut().unwrap().vacancy = SeatVacancy::Occupied
-                    }
-                    (Some(seat), 4..=8) if seat.vacancy == SeatVacancy::Occupied => {
-                        mutref.as_mut().unwrap().vacancy = SeatVacancy::Empty
-                    }
-                    _ => {
-                        continue;
-                    }
-                }
-                changed += 1;
-            }
-        }
-        if changed == 0 {
-            return grid
+    /// How many generations ran so far
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Runs a single generation, returning whether any seat changed
+    pub fn step(&mut self) -> bool {
+        let (occupied, tolerance) = (&self.occupied, self.tolerance);
+        let mut changed = false;
+        for (seat, next) in self.next.iter_mut().enumerate() {
+            let neighbours = self
                 .seats
+                .neighbours(seat)
                 .iter()
-                .flat_map(|s| s.iter())
-                .flat_map(|s| s)
-                .filter(|s| s.vacancy == SeatVacancy::Occupied)
+                .filter(|&&n| occupied[n])
                 .count();
+            *next = if occupied[seat] {
+                neighbours < tolerance.leave
+            } else {
+                neighbours <= tolerance.fill
+            };
+            changed |= *next != occupied[seat];
         }
+        mem::swap(&mut self.occupied, &mut self.next);
+        self.generation += 1;
+        changed
     }
-}
 
-#[aoc(day11, part2)]
-fn part2(input: &str) -> usize {
-    let mut grid = Grid::parse(input);
-    loop {
-        let mut iter = grid.iter_mut();
-        let mut changed = 0;
-        while let Some((mutref, shared, adjacent)) = iter.next_any() {
-            if mutref.is_some() {
-                match (
-                    shared,
-                    adjacent
-                        .iter()
-                        .flatten()
-                        .filter(|s| s.vacancy == SeatVacancy::Occupied)
-                        .count(),
-                ) {
-                    (Some(seat), 0) if seat.vacancy == SeatVacancy::Empty => {
-                        mutref.as_mut().unwrap().vacancy = SeatVacancy::Occupied
-                    }
-                    (Some(seat), 5..=8) if seat.vacancy == SeatVacancy::Occupied => {
-                        mutref.as_mut().unwrap().vacancy = SeatVacancy::Empty
-                    }
-                    _ => {
-                        continue;
-                    }
-                }
-                changed += 1;
+    /// Runs until a generation doesn't change anything, and returns how many generations did.
+    /// Some tolerances never settle: once the seats come back to an earlier arrangement,
+    /// this gives up with `None`, leaving the simulator somewhere in the cycle.
+    pub fn stabilize(&mut self) -> Option<usize> {
+        let start = self.generation;
+        // Brent's cycle detection: compare against a snapshot taken at each power of two
+        let mut earlier = self.occupied.clone();
+        let (mut power, mut steps) = (1, 0);
+        while self.step() {
+            if self.occupied == earlier {
+                return None;
+            }
+            steps += 1;
+            if steps == power {
+                earlier.clone_from(&self.occupied);
+                power *= 2;
+                steps = 0;
             }
         }
-        if changed == 0 {
-            return grid
-                .seats
-                .iter()
-                .flat_map(|s| s.iter())
-                .flat_map(|s| s)
-                .filter(|s| s.vacancy == SeatVacancy::Occupied)
-                .count();
-        }
+        Some(self.generation - start - 1)
     }
 }
 
+#[aoc(day11, part1)]
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let mut simulator = SeatSimulator::new(&parse_input(input)?, false, Tolerance::NEAR);
+    Ok(simulator.stabilize().map(|_| simulator.occupied()).into())
+}
+
+#[aoc(day11, part2)]
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let mut simulator = SeatSimulator::new(&parse_input(input)?, true, Tolerance::FAR);
+    Ok(simulator.stabilize().map(|_| simulator.occupied()).into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::life::{Life, Rule};
 
     #[test]
     fn test_grid() {
@@ -265,25 +161,12 @@ mod tests {
         // #..L
         let grid = "L.L#
 #..L";
-        let mut grid = Grid::parse(grid);
-        let mut iter = grid.iter_mut();
-        let entry = iter.next();
-        assert_eq!(
-            1,
-            entry
-                .unwrap()
-                .2
-                .iter()
-                .flatten()
-                .filter(|s| s.vacancy == SeatVacancy::Occupied)
-                .count()
-        );
-        let mut iter = grid.iter_mut();
-        let mut cnt = 0;
-        while let Some(_) = iter.next() {
-            cnt += 1;
-        }
-        assert_eq!(cnt, 8);
+        let grid = parse_input(grid).unwrap();
+        assert_eq!(grid.positions().count(), 8);
+        let seats = Seats::new(&grid.map(Option::is_some), false);
+        let occupied = seats.index_of(3, 0).into_iter().chain(seats.index_of(0, 1));
+        let life = Life::new(seats, Rule::new(&[0], &[0, 1, 2, 3]), occupied);
+        assert_eq!(1, life.live_neighbours(0));
     }
 
     #[test]
@@ -298,7 +181,7 @@ L.LLLLL.LL
 LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL";
-        assert_eq!(part1(grid), 37);
+        assert_eq!(part1(grid), Ok(Answer::Number(37)));
     }
 
     #[test]
@@ -313,6 +196,52 @@ L.LLLLL.LL
 LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL";
-        assert_eq!(part2(grid), 26);
+        assert_eq!(part2(grid), Ok(Answer::Number(26)));
+    }
+
+    #[test]
+    fn generations() {
+        let area = parse_input(
+            "L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLLL
+L.LLLLLL.L
+L.LLLLL.LL",
+        )
+        .unwrap();
+        let mut simulator = SeatSimulator::new(&area, false, Tolerance::NEAR);
+        assert!(simulator.step());
+        assert_eq!(simulator.occupied(), 71);
+        assert_eq!(simulator.stabilize(), Some(4));
+        assert_eq!((simulator.generation(), simulator.occupied()), (6, 37));
+        assert!(!simulator.step());
+
+        let mut simulator = SeatSimulator::new(&area, true, Tolerance::FAR);
+        assert_eq!(simulator.stabilize(), Some(6));
+        assert_eq!(simulator.occupied(), 26);
+
+        // Nobody minds a crowd, so every seat fills up and stays taken
+        let tolerance = Tolerance { fill: 8, leave: 9 };
+        let mut simulator = SeatSimulator::new(&area, false, tolerance);
+        assert_eq!(simulator.stabilize(), Some(1));
+        assert_eq!(simulator.occupied(), simulator.seats().len());
+    }
+
+    #[test]
+    fn oscillation() {
+        // Both seats fill up together, then both empty out together, forever
+        let area = parse_input("LL").unwrap();
+        let tolerance = Tolerance { fill: 0, leave: 1 };
+        let mut simulator = SeatSimulator::new(&area, false, tolerance);
+        assert_eq!(simulator.stabilize(), None);
+        assert!(simulator.generation() <= 4);
+
+        let area = parse_input("L.LL\n.L.L\nLLL.").unwrap();
+        assert_eq!(SeatSimulator::new(&area, true, tolerance).stabilize(), None);
     }
 }
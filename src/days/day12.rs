@@ -1,3 +1,8 @@
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 #[derive(Debug)]
 struct Location<'a> {
     x: i32, // east/west
@@ -100,32 +105,47 @@ impl<'a> Location<'a> {
     }
 }
 
+fn parse_input(input: &str) -> ParseResult<Vec<(char, i32)>> {
+    let src = Source::new(12, input);
+    input
+        .lines()
+        .map(|line| {
+            let action = src.expect(line.chars().next(), line, "an action")?;
+            let value = src.parse(&line[action.len_utf8()..])?;
+            match (action, value) {
+                ('N', _) | ('S', _) | ('E', _) | ('W', _) | ('F', _) => {}
+                ('L', 90) | ('L', 180) | ('L', 270) | ('R', 90) | ('R', 180) | ('R', 270) => {}
+                ('L', _) | ('R', _) => {
+                    return Err(src.error(line, "expected a turn of 90, 180 or 270 degrees"))
+                }
+                _ => return Err(src.error(&line[..action.len_utf8()], "invalid action")),
+            }
+            Ok((action, value))
+        })
+        .collect()
+}
+
 #[aoc(day12, part1)]
-fn part1(input: &str) -> i32 {
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
     let mut loc = Location::new();
-    for line in input.lines() {
-        let mut chars = line.chars();
-        let action = chars.next().unwrap();
-        let value = chars.collect::<String>().parse().unwrap();
+    for (action, value) in parse_input(input)? {
         loc.step(action, value);
     }
-    loc.x.abs() + loc.y.abs()
+    Ok((loc.x.abs() + loc.y.abs()).into())
 }
 
 #[aoc(day12, part2)]
-fn part2(input: &str) -> i32 {
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let actions = parse_input(input)?;
     let mut ship = Location::new();
     let ship = {
         let mut loc = Location::with_ship(&mut ship);
-        for line in input.lines() {
-            let mut chars = line.chars();
-            let action = chars.next().unwrap();
-            let value = chars.collect::<String>().parse().unwrap();
+        for (action, value) in actions {
             loc.step(action, value);
         }
         loc.ship.unwrap()
     };
-    ship.x.abs() + ship.y.abs()
+    Ok((ship.x.abs() + ship.y.abs()).into())
 }
 
 #[cfg(test)]
@@ -139,7 +159,7 @@ N3
 F7
 R90
 F11";
-        assert_eq!(part1(input), 25);
+        assert_eq!(part1(input), Ok(Answer::Number(25)));
     }
 
     #[test]
@@ -149,6 +169,6 @@ N3
 F7
 R90
 F11";
-        assert_eq!(part2(input), 286);
+        assert_eq!(part2(input), Ok(Answer::Number(286)));
     }
 }
@@ -1,40 +1,47 @@
 use std::cmp::Ordering;
 
-fn parse_input(input: &str) -> (i64, Vec<Option<i64>>) {
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
+fn parse_input(input: &str) -> ParseResult<(i64, Vec<Option<i64>>)> {
+    let src = Source::new(13, input);
     let mut lines = input.lines();
-    (
-        lines.next().map(|l| l.parse().unwrap()).unwrap(),
-        lines
-            .next()
-            .unwrap()
-            .split(',')
-            .map(|part| {
-                if part == "x" {
-                    None
-                } else {
-                    Some(part.parse().unwrap())
+    let timestamp = src.parse(src.expect(lines.next(), input, "a timestamp")?)?;
+    let buses = src.expect(lines.next(), input, "a list of bus IDs")?;
+    let times = buses
+        .split(',')
+        .map(|part| {
+            if part == "x" {
+                Ok(None)
+            } else {
+                match src.parse(part)? {
+                    id if id <= 0 => Err(src.error(part, "bus IDs must be positive")),
+                    id => Ok(Some(id)),
                 }
-            })
-            .collect(),
-    )
+            }
+        })
+        .collect::<ParseResult<_>>()?;
+    Ok((timestamp, times))
 }
 
 #[aoc(day13, part1)]
-fn part1(input: &str) -> i64 {
-    let (timestamp, times) = parse_input(input);
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let (timestamp, times) = parse_input(input)?;
     let res = times
         .into_iter()
         .flatten()
         .map(|i| (i, timestamp + i - (timestamp % i)))
-        .min_by(|x, y| x.1.cmp(&y.1))
-        .unwrap();
-    res.0 * (res.1 - timestamp)
+        .min_by(|x, y| x.1.cmp(&y.1));
+    // There may be no bus at all
+    Ok(res.map(|(id, time)| id * (time - timestamp)).into())
 }
 
 #[aoc(day13, part2)]
-fn part2(input: &str) -> i64 {
-    let (_, times) = parse_input(input);
-    times
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let (_, times) = parse_input(input)?;
+    Ok(times
         .into_iter()
         .enumerate()
         .filter_map(|(i, x)| x.map(|x| (x - (i as i64 + x - 1) % x - 1, x)))
@@ -42,6 +49,7 @@ fn part2(input: &str) -> i64 {
             chinese_remainder(r1, q1, r2, q2)
         })
         .0
+        .into())
 }
 
 fn gcd(a: i64, b: i64) -> i64 {
@@ -75,14 +83,14 @@ mod tests {
     fn part1_example() {
         let input = "939
 7,13,x,x,59,x,31,19";
-        assert_eq!(part1(input), 295);
+        assert_eq!(part1(input), Ok(Answer::Number(295)));
     }
 
     #[test]
     fn part2_examples() {
-        assert_eq!(part2("0\n17,x,13,19"), 3417);
-        assert_eq!(part2("0\n67,7,59,61"), 754018);
-        assert_eq!(part2("0\n67,x,7,59,61"), 779210);
-        assert_eq!(part2("0\n67,7,x,59,61"), 1261476);
+        assert_eq!(part2("0\n17,x,13,19"), Ok(Answer::Number(3417)));
+        assert_eq!(part2("0\n67,7,59,61"), Ok(Answer::Number(754018)));
+        assert_eq!(part2("0\n67,x,7,59,61"), Ok(Answer::Number(779210)));
+        assert_eq!(part2("0\n67,7,x,59,61"), Ok(Answer::Number(1261476)));
     }
 }
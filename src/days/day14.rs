@@ -1,8 +1,13 @@
 use fxhash::FxHashMap;
 use regex::Regex;
 
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 lazy_static::lazy_static! {
-    static ref REGEX: Regex = Regex::new(r#"m(?:ask = ([01X]+)|em\[(\d+)\] = (\d+))"#).unwrap();
+    static ref REGEX: Regex = Regex::new(r#"^m(?:ask = ([01X]{36})|em\[(\d+)\] = (\d+))$"#).unwrap();
 }
 
 enum Instruction<'a> {
@@ -10,26 +15,35 @@ enum Instruction<'a> {
     Set(u64, u64),
 }
 
-fn parse_input(input: &str) -> Vec<Instruction<'_>> {
+fn parse_input(input: &str) -> ParseResult<Vec<Instruction<'_>>> {
+    let src = Source::new(14, input);
     input
         .lines()
         .map(|l| {
-            let caps = REGEX.captures(l).expect("input doesn't match pattern");
-            match &caps.get(1) {
+            let caps = REGEX.captures(l).ok_or_else(|| {
+                src.error(
+                    l,
+                    "expected `mask = <36 bits>` or `mem[<address>] = <value>`",
+                )
+            })?;
+            Ok(match &caps.get(1) {
                 Some(mask) => Instruction::Mask(mask.as_str()),
-                None => Instruction::Set(caps[2].parse().unwrap(), caps[3].parse().unwrap()),
-            }
+                None => Instruction::Set(
+                    src.parse(caps.get(2).unwrap().as_str())?,
+                    src.parse(caps.get(3).unwrap().as_str())?,
+                ),
+            })
         })
         .collect()
 }
 
 #[aoc(day14, part1)]
-fn part1(input: &str) -> u64 {
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
     // We can represent the "36-bit address space" as an u64 and just ignore the 28 most significant bits.
     // When we use left-shift we have to invert the bit index to avoid adding zeros on the back instead.
     let mut space: FxHashMap<u64, u64> = FxHashMap::default();
     let mut mask = "";
-    let instructions = parse_input(input);
+    let instructions = parse_input(input)?;
     for inst in instructions {
         match inst {
             Instruction::Set(k, mut v) => {
@@ -45,16 +59,16 @@ fn part1(input: &str) -> u64 {
             Instruction::Mask(m) => mask = m,
         }
     }
-    space.values().sum()
+    Ok(space.values().sum::<u64>().into())
 }
 
 #[aoc(day14, part2)]
-fn part2(input: &str) -> u64 {
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
     // We can represent the "36-bit address space" as an u64 and just ignore the 28 most significant bits.
     // When we use left-shift we have to invert the bit index to avoid adding zeros on the back instead.
     let mut space: FxHashMap<String, u64> = FxHashMap::default();
     let mut mask = "";
-    let instructions = parse_input(input);
+    let instructions = parse_input(input)?;
     for inst in instructions {
         match inst {
             Instruction::Set(k, v) => {
@@ -86,7 +100,7 @@ fn part2(input: &str) -> u64 {
             Instruction::Mask(m) => mask = m,
         }
     }
-    space.values().sum()
+    Ok(space.values().sum::<u64>().into())
 }
 
 #[cfg(test)]
@@ -99,7 +113,7 @@ mod tests {
 mem[8] = 11
 mem[7] = 101
 mem[8] = 0";
-        assert_eq!(part1(input), 165);
+        assert_eq!(part1(input), Ok(Answer::Number(165)));
     }
 
     #[test]
@@ -108,6 +122,6 @@ mem[8] = 0";
 mem[42] = 100
 mask = 00000000000000000000000000000000X0XX
 mem[26] = 1";
-        assert_eq!(part2(input), 208);
+        assert_eq!(part2(input), Ok(Answer::Number(208)));
     }
 }
@@ -1,7 +1,13 @@
 use fxhash::FxHashMap;
 
-fn parse_input(input: &str) -> Vec<u32> {
-    input.split(',').map(|p| p.parse().unwrap()).collect()
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
+fn parse_input(input: &str) -> ParseResult<Vec<u32>> {
+    let src = Source::new(15, input);
+    input.split(',').map(|p| src.parse(p)).collect()
 }
 
 fn calc(starting_nums: &[u32], end: u32) -> u32 {
@@ -35,13 +41,13 @@ fn calc(starting_nums: &[u32], end: u32) -> u32 {
 }
 
 #[aoc(day15, part1)]
-fn part1(input: &str) -> u32 {
-    calc(&parse_input(input), 2020)
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    Ok(calc(&parse_input(input)?, 2020).into())
 }
 
 #[aoc(day15, part2)]
-fn part2(input: &str) -> u32 {
-    calc(&parse_input(input), 30000000)
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    Ok(calc(&parse_input(input)?, 30000000).into())
 }
 
 #[cfg(test)]
@@ -50,7 +56,7 @@ mod tests {
 
     #[test]
     fn part1_examples() {
-        assert_eq!(part1("0,3,6"), 436);
-        assert_eq!(part1("1,3,2"), 1);
+        assert_eq!(part1("0,3,6"), Ok(Answer::Number(436)));
+        assert_eq!(part1("1,3,2"), Ok(Answer::Number(1)));
     }
 }
@@ -3,6 +3,12 @@ use std::ops::RangeInclusive;
 use fxhash::FxHashMap;
 use regex::Regex;
 
+use crate::{
+    constraint,
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 struct Input {
     fields: FxHashMap<String, (RangeInclusive<u64>, RangeInclusive<u64>)>,
     own_ticket: Vec<u64>,
@@ -13,41 +19,52 @@ lazy_static::lazy_static! {
     static ref FIELD_VALIDITY: Regex = Regex::new(r#"(.+): (\d+)-(\d+) or (\d+)-(\d+)"#).unwrap();
 }
 
-fn parse_input(input: &str) -> Input {
+fn parse_ticket(line: &str, fields: usize, src: &Source) -> ParseResult<Vec<u64>> {
+    let ticket = line
+        .split(',')
+        .map(|l| src.parse(l))
+        .collect::<ParseResult<Vec<_>>>()?;
+    if ticket.len() != fields {
+        return Err(src.error(line, format!("expected {} values", fields)));
+    }
+    Ok(ticket)
+}
+
+fn parse_input(input: &str) -> ParseResult<Input> {
+    let src = Source::new(16, input);
     let mut fields = FxHashMap::default();
     let mut nearby_tickets = vec![];
-    let parts: Vec<&str> = input.split("\n\n").collect();
-    for line in parts[0].lines() {
-        if let Some(caps) = FIELD_VALIDITY.captures(line) {
-            let name = String::from(&caps[1]);
-            let ranges = (
-                caps[2].parse().unwrap()..=caps[3].parse().unwrap(),
-                caps[4].parse().unwrap()..=caps[5].parse().unwrap(),
-            );
-            fields.insert(name, ranges);
-        }
+    let mut parts = input.split("\n\n");
+    for line in parts.next().unwrap().lines() {
+        let caps = FIELD_VALIDITY
+            .captures(line)
+            .ok_or_else(|| src.error(line, "expected `<field>: <a>-<b> or <c>-<d>`"))?;
+        let bound = |i| src.parse(caps.get(i).unwrap().as_str());
+        let name = String::from(&caps[1]);
+        let ranges = (bound(2)?..=bound(3)?, bound(4)?..=bound(5)?);
+        fields.insert(name, ranges);
     }
-    let own_ticket = parts[1]
-        .lines()
-        .nth(1)
-        .unwrap()
-        .split(',')
-        .map(|l| l.parse().unwrap())
-        .collect();
-    for line in parts[2].lines().skip(1) {
-        nearby_tickets.push(line.split(',').map(|l| l.parse().unwrap()).collect());
+    let own = src.expect(parts.next(), input, "your ticket")?;
+    let own_ticket = parse_ticket(
+        src.expect(own.lines().nth(1), own, "your ticket's values")?,
+        fields.len(),
+        &src,
+    )?;
+    let nearby = src.expect(parts.next(), input, "nearby tickets")?;
+    for line in nearby.lines().skip(1) {
+        nearby_tickets.push(parse_ticket(line, fields.len(), &src)?);
     }
-    Input {
+    Ok(Input {
         fields,
         own_ticket,
         nearby_tickets,
-    }
+    })
 }
 
 #[aoc(day16, part1)]
-fn part1(input: &str) -> u64 {
-    let input = parse_input(input);
-    input
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let input = parse_input(input)?;
+    Ok(input
         .nearby_tickets
         .iter()
         .flatten()
@@ -57,12 +74,13 @@ fn part1(input: &str) -> u64 {
                 .values()
                 .all(|range| !range.0.contains(i) && !range.1.contains(i))
         })
-        .sum()
+        .sum::<u64>()
+        .into())
 }
 
 #[aoc(day16, part2)]
-fn part2(input: &str) -> u64 {
-    let input = parse_input(input);
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let input = parse_input(input)?;
     let mut valid: Vec<_> = input
         .nearby_tickets
         .iter()
@@ -71,7 +89,7 @@ fn part2(input: &str) -> u64 {
                 input
                     .fields
                     .values()
-                    .any(|ranges| ranges.0.contains(&i) || ranges.1.contains(&i))
+                    .any(|ranges| ranges.0.contains(i) || ranges.1.contains(i))
             })
         })
         .collect();
@@ -90,35 +108,17 @@ fn part2(input: &str) -> u64 {
         }
         res.push((s, possibilities));
     }
-    let old_snap = res.clone();
-    for (idx, possibilities) in res.iter_mut().enumerate() {
-        *possibilities = (
-            possibilities.0,
-            possibilities
-                .1
-                .iter()
-                .filter(|i| {
-                    !old_snap
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(idx2, x)| if idx2 != idx { Some(x) } else { None })
-                        .all(|v| v.1.contains(i))
-                })
-                .map(|&v| v)
-                .collect::<Vec<usize>>(),
-        );
-    }
-    // Reduce possibilities to one each
-    crate::reduce_possibilities(&mut res);
-    res.into_iter()
-        .filter_map(|v| {
-            if v.0.starts_with("departure") {
-                Some(input.own_ticket[v.1[0]])
-            } else {
-                None
-            }
-        })
-        .product()
+    // Fields that can't be told apart leave the puzzle without an answer
+    let fields = match constraint::solve(&res) {
+        Ok(fields) => fields,
+        Err(_) => return Ok(Answer::NoSolution),
+    };
+    Ok(fields
+        .into_iter()
+        .filter(|(name, _)| name.starts_with("departure"))
+        .map(|(_, idx)| input.own_ticket[idx])
+        .product::<u64>()
+        .into())
 }
 
 #[cfg(test)]
@@ -139,6 +139,36 @@ nearby tickets:
 40,4,50
 55,2,20
 38,6,12";
-        assert_eq!(part1(input), 71);
+        assert_eq!(part1(input), Ok(Answer::Number(71)));
+    }
+
+    #[test]
+    fn part2_example() {
+        let input = "departure class: 0-1 or 4-19
+row: 0-5 or 8-19
+departure seat: 0-13 or 16-19
+
+your ticket:
+11,12,13
+
+nearby tickets:
+3,9,18
+15,1,5
+5,14,9";
+        assert_eq!(part2(input), Ok(Answer::Number(12 * 13)));
+    }
+
+    #[test]
+    fn no_solution() {
+        // Both fields fit both columns
+        let input = "departure class: 0-19 or 20-20
+row: 0-19 or 20-20
+
+your ticket:
+11,12
+
+nearby tickets:
+3,9";
+        assert_eq!(part2(input), Ok(Answer::NoSolution));
     }
 }
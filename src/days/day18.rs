@@ -1,10 +1,53 @@
 use itertools::Itertools;
 use regex::Regex;
 
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 lazy_static::lazy_static! {
     /// Get the input inside parentheses. If they're nested, it gets the innermost one so it can recursively simplify the input.
     static ref PARENTHESES: Regex = Regex::new(r#"\([^()]+\)"#).unwrap();
     static ref INPUT_TRANSFORM: Regex = Regex::new(r#"\d+ \+ \d+"#).unwrap();
+    static ref OPERAND: Regex = Regex::new(r#"^(\(*)\d+(\)*)$"#).unwrap();
+}
+
+/// Checks that every line only has numbers (with parentheses attached) separated by ` + ` or ` * `,
+/// which is the only format `run_operations` can handle.
+fn parse_input(input: &str) -> ParseResult<Vec<&str>> {
+    let src = Source::new(18, input);
+    input
+        .lines()
+        .map(|line| {
+            let mut depth = 0;
+            let mut expect_operand = true;
+            for token in line.split(' ') {
+                match token {
+                    "+" | "*" if !expect_operand => expect_operand = true,
+                    _ if expect_operand => {
+                        let caps = OPERAND
+                            .captures(token)
+                            .ok_or_else(|| src.error(token, "expected a number"))?;
+                        depth += caps[1].len();
+                        if caps[2].len() > depth {
+                            return Err(src.error(token, "unbalanced parentheses"));
+                        }
+                        depth -= caps[2].len();
+                        expect_operand = false;
+                    }
+                    _ => return Err(src.error(token, "expected `+` or `*`")),
+                }
+            }
+            if expect_operand {
+                Err(src.error(&line[line.len()..], "expected a number"))
+            } else if depth != 0 {
+                Err(src.error(line, "unbalanced parentheses"))
+            } else {
+                Ok(line)
+            }
+        })
+        .collect()
 }
 
 fn run_operations(input: &str, other_precendence: bool) -> u64 {
@@ -48,13 +91,21 @@ fn run_operations(input: &str, other_precendence: bool) -> u64 {
 }
 
 #[aoc(day18, part1)]
-fn part1(input: &str) -> u64 {
-    input.lines().map(|l| run_operations(l, false)).sum()
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    Ok(parse_input(input)?
+        .into_iter()
+        .map(|l| run_operations(l, false))
+        .sum::<u64>()
+        .into())
 }
 
 #[aoc(day18, part2)]
-fn part2(input: &str) -> u64 {
-    input.lines().map(|l| run_operations(l, true)).sum()
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    Ok(parse_input(input)?
+        .into_iter()
+        .map(|l| run_operations(l, true))
+        .sum::<u64>()
+        .into())
 }
 
 #[cfg(test)]
@@ -63,23 +114,44 @@ mod tests {
 
     #[test]
     fn part1_examples() {
-        assert_eq!(part1("2 * 3 + (4 * 5)"), 26);
-        assert_eq!(part1("5 + (8 * 3 + 9 + 3 * 4 * 3)"), 437);
-        assert_eq!(part1("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))"), 12240);
+        assert_eq!(part1("2 * 3 + (4 * 5)"), Ok(Answer::Number(26)));
+        assert_eq!(
+            part1("5 + (8 * 3 + 9 + 3 * 4 * 3)"),
+            Ok(Answer::Number(437))
+        );
+        assert_eq!(
+            part1("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))"),
+            Ok(Answer::Number(12240))
+        );
         assert_eq!(
             part1("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2"),
-            13632
+            Ok(Answer::Number(13632))
         );
     }
 
+    #[test]
+    fn invalid_expressions() {
+        assert!(part1("2 * (3 + 4").is_err());
+        assert!(part1("2 * 3)").is_err());
+        assert!(part1("2 * 3 +").is_err());
+        let err = part1("1 + 2\n2 - 3").unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (2, 3, "-"));
+    }
+
     #[test]
     fn part2_examples() {
-        assert_eq!(part2("2 * 3 + (4 * 5)"), 46);
-        assert_eq!(part2("5 + (8 * 3 + 9 + 3 * 4 * 3)"), 1445);
-        assert_eq!(part2("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))"), 669060);
+        assert_eq!(part2("2 * 3 + (4 * 5)"), Ok(Answer::Number(46)));
+        assert_eq!(
+            part2("5 + (8 * 3 + 9 + 3 * 4 * 3)"),
+            Ok(Answer::Number(1445))
+        );
+        assert_eq!(
+            part2("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))"),
+            Ok(Answer::Number(669060))
+        );
         assert_eq!(
             part2("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2"),
-            23340
+            Ok(Answer::Number(23340))
         );
     }
 }
@@ -1,8 +1,13 @@
 use fxhash::FxHashMap;
 use regex::Regex;
 
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 lazy_static::lazy_static! {
-    static ref RULE: Regex = Regex::new(r#"(\d+): (?:"(.+)"|(.+) \| (.+)|(.+))"#).unwrap();
+    static ref RULE: Regex = Regex::new(r#"^(\d+): (?:"(.)"|(.+) \| (.+)|(.+))$"#).unwrap();
 }
 
 #[derive(Debug, Clone)]
@@ -22,7 +27,7 @@ impl Matcher {
         for rule in rules {
             let mut new_msgs = vec![];
             for message in messages {
-                new_msgs.append(&mut self.matches(&message, *rule))
+                new_msgs.append(&mut self.matches(message, *rule))
             }
             messages = new_msgs;
         }
@@ -36,64 +41,68 @@ impl Matcher {
         match &self.rules[&rule] {
             Rule::Simple(c) => message
                 .strip_prefix(*c)
-                .and_then(|m| Some(vec![m]))
-                .unwrap_or_else(|| vec![]),
-            Rule::Multiple(rules) => self.multiple_match(message, &rules),
+                .map(|m| vec![m])
+                .unwrap_or_default(),
+            Rule::Multiple(rules) => self.multiple_match(message, rules),
             Rule::Either(possible) => possible
                 .iter()
-                .map(|p| self.multiple_match(message, p))
-                .flatten()
+                .flat_map(|p| self.multiple_match(message, p))
                 .collect(),
         }
     }
 }
 
-fn parse_input(input: &str) -> (Matcher, Vec<&'_ str>) {
+fn parse_input(input: &str) -> ParseResult<(Matcher, Vec<&'_ str>)> {
+    let src = Source::new(19, input);
     let mut parts = input.split("\n\n");
     let rules = parts.next().unwrap();
-    let messages = parts.next().unwrap();
+    let messages = src.expect(parts.next(), input, "a list of messages")?;
+    let sequence = |list: &str| {
+        list.split(' ')
+            .map(|c| src.parse(c))
+            .collect::<ParseResult<Vec<u8>>>()
+    };
     let mut map = FxHashMap::default();
     for rule in rules.lines() {
-        let caps = RULE.captures(rule).unwrap();
-        let id = caps[1].parse().unwrap();
+        let caps = RULE
+            .captures(rule)
+            .ok_or_else(|| src.error(rule, "expected `<id>: <rule>`"))?;
+        let id = src.parse(caps.get(1).unwrap().as_str())?;
         if let Some(simple) = caps.get(2) {
             map.insert(id, Rule::Simple(simple.as_str().chars().next().unwrap()));
         } else if let Some(one) = caps.get(3) {
             map.insert(
                 id,
                 Rule::Either(vec![
-                    one.as_str()
-                        .split(' ')
-                        .map(|c| c.parse().unwrap())
-                        .collect(),
-                    caps.get(4)
-                        .unwrap()
-                        .as_str()
-                        .split(' ')
-                        .map(|c| c.parse().unwrap())
-                        .collect(),
+                    sequence(one.as_str())?,
+                    sequence(caps.get(4).unwrap().as_str())?,
                 ]),
             );
         } else if let Some(multiple) = caps.get(5) {
-            map.insert(
-                id,
-                Rule::Multiple(
-                    multiple
-                        .as_str()
-                        .split(' ')
-                        .map(|c| c.parse().unwrap())
-                        .collect(),
-                ),
-            );
+            map.insert(id, Rule::Multiple(sequence(multiple.as_str())?));
         }
     }
-    (Matcher { rules: map }, messages.lines().collect())
+    // Make sure every referenced rule exists, so matching can't fail
+    for rule in rules.lines() {
+        let body = &rule[rule.find(':').unwrap() + 1..];
+        for part in body.split(' ') {
+            if let Ok(id) = part.parse::<u8>() {
+                if !map.contains_key(&id) {
+                    return Err(src.error(part, "reference to an undefined rule"));
+                }
+            }
+        }
+    }
+    if !map.contains_key(&0) {
+        return Err(src.error(rules, "missing rule 0"));
+    }
+    Ok((Matcher { rules: map }, messages.lines().collect()))
 }
 
 #[aoc(day19, part1)]
-fn part1(input: &str) -> usize {
-    let (matcher, messages) = parse_input(input);
-    messages
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let (matcher, messages) = parse_input(input)?;
+    Ok(messages
         .into_iter()
         .map(|m| {
             matcher
@@ -102,19 +111,20 @@ fn part1(input: &str) -> usize {
                 .filter(|v| v.is_empty())
                 .count()
         })
-        .sum()
+        .sum::<usize>()
+        .into())
 }
 
 #[aoc(day19, part2)]
-fn part2(input: &str) -> usize {
-    let (mut matcher, messages) = parse_input(input);
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let (mut matcher, messages) = parse_input(input)?;
     matcher
         .rules
         .insert(8, Rule::Either(vec![vec![42], vec![42, 8]]));
     matcher
         .rules
         .insert(11, Rule::Either(vec![vec![42, 31], vec![42, 11, 31]]));
-    messages
+    Ok(messages
         .into_iter()
         .map(|m| {
             matcher
@@ -123,7 +133,8 @@ fn part2(input: &str) -> usize {
                 .filter(|v| v.is_empty())
                 .count()
         })
-        .sum()
+        .sum::<usize>()
+        .into())
 }
 
 #[cfg(test)]
@@ -144,6 +155,6 @@ bababa
 abbbab
 aaabbb
 aaaabbb"#;
-        assert_eq!(part1(input), 2);
+        assert_eq!(part1(input), Ok(Answer::Number(2)));
     }
 }
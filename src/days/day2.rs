@@ -1,65 +1,478 @@
+//! Password policies. Besides the two policies of the puzzle, policies can be combined
+//! and read from a policy file (see [`PolicySet::parse`]), then checked against a password database.
+
 use regex::Regex;
 
-#[derive(Debug)]
-struct Password {
-    min: usize,
-    max: usize,
-    letter: char,
-    input: Vec<char>,
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+    tokens::Tokens,
+};
+
+lazy_static::lazy_static! {
+    static ref ENTRY: Regex = Regex::new(r"^(\d+)-(\d+) (\S): (\S+)$").unwrap();
+}
+
+/// A line of the password database: the policy numbers and letter, then the password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub min: usize,
+    pub max: usize,
+    pub letter: char,
+    pub password: String,
+}
+
+pub trait PasswordPolicy {
+    /// What the policy requires, e.g. "at least 8 characters"
+    fn describe(&self) -> String;
+
+    fn allows(&self, entry: &Entry) -> bool;
+
+    /// The rule that `entry` breaks, if any
+    fn violation(&self, entry: &Entry) -> Option<String> {
+        if self.allows(entry) {
+            None
+        } else {
+            Some(self.describe())
+        }
+    }
+}
+
+/// The sled rental policy (part 1): the letter appears between `min` and `max` times
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LetterCount;
+
+impl PasswordPolicy for LetterCount {
+    fn describe(&self) -> String {
+        "the letter must appear between min and max times".to_string()
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        let count = entry
+            .password
+            .chars()
+            .filter(|&c| c == entry.letter)
+            .count();
+        (entry.min..=entry.max).contains(&count)
+    }
+
+    fn violation(&self, entry: &Entry) -> Option<String> {
+        if self.allows(entry) {
+            return None;
+        }
+        Some(format!(
+            "`{}` must appear {} to {} times",
+            entry.letter, entry.min, entry.max
+        ))
+    }
+}
+
+/// The toboggan policy (part 2): the letter is at exactly one of the positions `min` and `max`, counting from 1
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LetterPosition;
+
+impl PasswordPolicy for LetterPosition {
+    fn describe(&self) -> String {
+        "the letter must be at exactly one of the positions min and max".to_string()
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        let at = |pos: usize| entry.password.chars().nth(pos - 1) == Some(entry.letter);
+        at(entry.min) ^ at(entry.max)
+    }
+
+    fn violation(&self, entry: &Entry) -> Option<String> {
+        if self.allows(entry) {
+            return None;
+        }
+        Some(format!(
+            "`{}` must be at exactly one of the positions {} and {}",
+            entry.letter, entry.min, entry.max
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MinLength(pub usize);
+
+impl PasswordPolicy for MinLength {
+    fn describe(&self) -> String {
+        format!("at least {} characters", self.0)
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        entry.password.chars().count() >= self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Lower,
+    Upper,
+    Digit,
+    /// Anything that isn't a letter or a digit
+    Symbol,
+}
+
+impl CharClass {
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            CharClass::Lower => c.is_lowercase(),
+            CharClass::Upper => c.is_uppercase(),
+            CharClass::Digit => c.is_ascii_digit(),
+            CharClass::Symbol => !c.is_alphanumeric(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CharClass::Lower => "lower",
+            CharClass::Upper => "upper",
+            CharClass::Digit => "digit",
+            CharClass::Symbol => "symbol",
+        }
+    }
+}
+
+/// At least one character of each class
+#[derive(Debug, Clone)]
+pub struct RequiredClasses(pub Vec<CharClass>);
+
+impl PasswordPolicy for RequiredClasses {
+    fn describe(&self) -> String {
+        let names: Vec<_> = self.0.iter().map(|c| c.name()).collect();
+        format!("at least one {} character", names.join(", one "))
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        self.0
+            .iter()
+            .all(|&class| entry.password.chars().any(|c| class.contains(c)))
+    }
+
+    fn violation(&self, entry: &Entry) -> Option<String> {
+        let missing = self
+            .0
+            .iter()
+            .find(|&&class| !entry.password.chars().any(|c| class.contains(c)))?;
+        Some(format!("at least one {} character", missing.name()))
+    }
 }
 
-impl Password {
-    fn valid_part1(&self) -> bool {
-        (self.min..=self.max).contains(&self.input.iter().filter(|c| **c == self.letter).count())
+/// None of the substrings
+#[derive(Debug, Clone)]
+pub struct Forbidden(pub Vec<String>);
+
+impl PasswordPolicy for Forbidden {
+    fn describe(&self) -> String {
+        format!("must not contain {:?}", self.0)
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        self.violation(entry).is_none()
     }
 
-    fn valid_part2(&self) -> bool {
-        (self.input[self.min - 1] == self.letter) ^ (self.input[self.max - 1] == self.letter)
+    fn violation(&self, entry: &Entry) -> Option<String> {
+        let found = self
+            .0
+            .iter()
+            .find(|s| entry.password.contains(s.as_str()))?;
+        Some(format!("must not contain {:?}", found))
     }
 }
 
-fn parse_input(input: &str) -> Vec<Password> {
-    let regex = Regex::new("(\\d+)-(\\d+) ([a-z]): ([a-z]+)").unwrap();
+/// Every policy, reporting the first one that's violated
+pub struct All(pub Vec<Box<dyn PasswordPolicy>>);
+
+impl PasswordPolicy for All {
+    fn describe(&self) -> String {
+        let parts: Vec<_> = self.0.iter().map(|p| p.describe()).collect();
+        parts.join(" and ")
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        self.0.iter().all(|p| p.allows(entry))
+    }
+
+    fn violation(&self, entry: &Entry) -> Option<String> {
+        self.0.iter().find_map(|p| p.violation(entry))
+    }
+}
+
+/// At least one of the policies
+pub struct Any(pub Vec<Box<dyn PasswordPolicy>>);
+
+impl PasswordPolicy for Any {
+    fn describe(&self) -> String {
+        let parts: Vec<_> = self.0.iter().map(|p| p.describe()).collect();
+        format!("either {}", parts.join(" or "))
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        self.0.iter().any(|p| p.allows(entry))
+    }
+}
+
+pub struct Not(pub Box<dyn PasswordPolicy>);
+
+impl PasswordPolicy for Not {
+    fn describe(&self) -> String {
+        format!("not: {}", self.0.describe())
+    }
+
+    fn allows(&self, entry: &Entry) -> bool {
+        !self.0.allows(entry)
+    }
+}
+
+fn policy(tokens: &mut Tokens) -> ParseResult<Box<dyn PasswordPolicy>> {
+    let name = tokens.token("a policy")?;
+    Ok(match name {
+        "count" => Box::new(LetterCount),
+        "positions" => Box::new(LetterPosition),
+        "all" | "any" | "not" => {
+            let mut policies = tokens.list(policy)?;
+            match name {
+                "all" => Box::new(All(policies)),
+                "any" => Box::new(Any(policies)),
+                _ if policies.len() == 1 => Box::new(Not(policies.pop().unwrap())),
+                _ => return Err(tokens.error(name, "`not` takes a single policy")),
+            }
+        }
+        "min_length" => match tokens.args()[..] {
+            [n] => Box::new(MinLength(tokens.src().parse(n)?)),
+            _ => return Err(tokens.error(name, "expected `min_length <length>`")),
+        },
+        "classes" => {
+            let classes = tokens
+                .args()
+                .into_iter()
+                .map(|arg| match arg {
+                    "lower" => Ok(CharClass::Lower),
+                    "upper" => Ok(CharClass::Upper),
+                    "digit" => Ok(CharClass::Digit),
+                    "symbol" => Ok(CharClass::Symbol),
+                    _ => Err(tokens.error(arg, "expected `lower`, `upper`, `digit` or `symbol`")),
+                })
+                .collect::<ParseResult<Vec<_>>>()?;
+            if classes.is_empty() {
+                return Err(tokens.error(name, "expected at least one class"));
+            }
+            Box::new(RequiredClasses(classes))
+        }
+        "forbid" => {
+            let substrings = tokens
+                .args()
+                .into_iter()
+                .map(|arg| {
+                    arg.strip_prefix('"')
+                        .and_then(|s| s.strip_suffix('"'))
+                        .map(str::to_string)
+                        .ok_or_else(|| tokens.error(arg, "expected a quoted string"))
+                })
+                .collect::<ParseResult<Vec<_>>>()?;
+            if substrings.is_empty() {
+                return Err(tokens.error(name, "expected at least one string"));
+            }
+            Box::new(Forbidden(substrings))
+        }
+        _ => return Err(tokens.error(name, "unknown policy")),
+    })
+}
+
+/// Named policies, to check a database against
+#[derive(Default)]
+pub struct PolicySet(pub Vec<(String, Box<dyn PasswordPolicy>)>);
+
+/// How a database fared against one policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    pub policy: String,
+    pub valid: usize,
+    /// The index of each invalid entry, with the rule it broke
+    pub violations: Vec<(usize, String)>,
+}
+
+impl PolicySet {
+    /// Reads a policy file, with one `<name> = <policy>` per line, like:
+    ///
+    /// ```text
+    /// # The puzzle's policies
+    /// sled = count
+    /// toboggan = positions
+    /// strong = all(min_length 8, classes lower digit, forbid "password" "1234")
+    /// lenient = any(count, positions)
+    /// no_digits = not(classes digit)
+    /// ```
+    pub fn parse(input: &str) -> ParseResult<PolicySet> {
+        let src = Source::new(0, input);
+        let mut policies = vec![];
+        for line in input.lines() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let mut tokens = Tokens::new(&src, line)?;
+            if tokens.is_empty() {
+                continue;
+            }
+            let name = tokens.token("a name")?;
+            tokens.expect("=")?;
+            let policy = policy(&mut tokens)?;
+            tokens.finish("the end of the line")?;
+            policies.push((name.to_string(), policy));
+        }
+        Ok(PolicySet(policies))
+    }
+
+    pub fn evaluate(&self, entries: &[Entry]) -> Vec<Evaluation> {
+        self.0
+            .iter()
+            .map(|(name, policy)| {
+                let violations: Vec<_> = entries
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, entry)| policy.violation(entry).map(|v| (i, v)))
+                    .collect();
+                Evaluation {
+                    policy: name.clone(),
+                    valid: entries.len() - violations.len(),
+                    violations,
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn parse_database(input: &str) -> ParseResult<Vec<Entry>> {
+    let src = Source::new(2, input);
     input
         .lines()
         .map(|line| {
-            let caps = regex.captures(line).unwrap();
-            Password {
-                min: caps[1].parse().unwrap(),
-                max: caps[2].parse().unwrap(),
+            let caps = ENTRY
+                .captures(line)
+                .ok_or_else(|| src.error(line, "expected `<min>-<max> <letter>: <password>`"))?;
+            let entry = Entry {
+                min: src.parse(caps.get(1).unwrap().as_str())?,
+                max: src.parse(caps.get(2).unwrap().as_str())?,
                 letter: caps[3].chars().next().unwrap(),
-                input: caps[4].chars().collect(),
+                password: caps[4].to_string(),
+            };
+            if entry.min == 0 || entry.min > entry.max {
+                return Err(src.error(line, "invalid range"));
             }
+            Ok(entry)
         })
         .collect()
 }
 
-#[aoc(day2, part1)]
-pub fn part1(input: &str) -> usize {
-    parse_input(input)
+fn count_valid(input: &str, policy: &dyn PasswordPolicy) -> ParseResult<Answer> {
+    Ok(parse_database(input)?
         .iter()
-        .filter(|password| password.valid_part1())
+        .filter(|entry| policy.allows(entry))
         .count()
+        .into())
+}
+
+#[aoc(day2, part1)]
+pub fn part1(input: &str) -> Result<Answer, ParseError> {
+    count_valid(input, &LetterCount)
 }
 
 #[aoc(day2, part2)]
-pub fn part2(input: &str) -> usize {
-    parse_input(input)
-        .iter()
-        .filter(|password| password.valid_part2())
-        .count()
+pub fn part2(input: &str) -> Result<Answer, ParseError> {
+    count_valid(input, &LetterPosition)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
     #[test]
     fn part1_example() {
-        assert_eq!(part1("1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc"), 2);
+        assert_eq!(
+            part1("1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc"),
+            Ok(Answer::Number(2))
+        );
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2("1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc"), 1);
+        assert_eq!(
+            part2("1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc"),
+            Ok(Answer::Number(1))
+        );
+    }
+
+    #[test]
+    fn any_characters() {
+        let entries = parse_database("1-2 é: éA#é\n2-3 !: x!!").unwrap();
+        assert_eq!(entries[0].letter, 'é');
+        assert!(LetterCount.allows(&entries[0]));
+        assert!(LetterPosition.allows(&entries[0]));
+        assert!(LetterCount.allows(&entries[1]));
+        assert!(!LetterPosition.allows(&entries[1]));
+    }
+
+    #[test]
+    fn policy_file() {
+        let policies = PolicySet::parse(
+            "# Puzzle policies
+sled = count
+strong = all(min_length 6, classes lower digit, forbid \"abc\" \"123\")
+either = any(count, positions)",
+        )
+        .unwrap();
+        let entries =
+            parse_database("1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc\n1-2 x: x2y4z6").unwrap();
+        let evaluations = policies.evaluate(&entries);
+        assert_eq!(evaluations[0].policy, "sled");
+        assert_eq!(evaluations[0].valid, 3);
+        assert_eq!(
+            evaluations[0].violations,
+            [(1, "`b` must appear 1 to 3 times".to_string())]
+        );
+        assert_eq!(evaluations[1].valid, 1);
+        assert_eq!(
+            evaluations[1].violations,
+            [
+                (0, "at least 6 characters".to_string()),
+                (1, "at least 6 characters".to_string()),
+                (2, "at least one digit character".to_string())
+            ]
+        );
+        assert_eq!(evaluations[2].valid, 3);
+        assert_eq!(
+            evaluations[2].violations[0].1,
+            "either the letter must appear between min and max times or the letter must be at exactly one of the positions min and max"
+        );
+    }
+
+    #[test]
+    fn negated_policies() {
+        let policies = PolicySet::parse("weak = not(forbid \"abc\" \"123\")").unwrap();
+        let entries = parse_database("1-1 a: xyz").unwrap();
+        assert_eq!(
+            policies.evaluate(&entries)[0].violations[0].1,
+            "not: must not contain [\"abc\", \"123\"]"
+        );
+    }
+
+    #[test]
+    fn invalid_policies() {
+        let err = PolicySet::parse("a = count\nb = all(count, maybe)")
+            .err()
+            .unwrap();
+        assert_eq!(
+            (err.line, err.column, err.reason.as_str()),
+            (2, 16, "unknown policy")
+        );
+        let err = PolicySet::parse("a = not(count, positions)").err().unwrap();
+        assert_eq!(err.reason, "`not` takes a single policy");
+        let err = PolicySet::parse("a = classes lower caps").err().unwrap();
+        assert_eq!(err.text, "caps");
+        let err = PolicySet::parse("a = all(count").err().unwrap();
+        assert_eq!(err.reason, "expected `)`");
+        let err = PolicySet::parse("a = forbid \"x").err().unwrap();
+        assert_eq!(err.reason, "unterminated string");
     }
 }
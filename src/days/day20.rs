@@ -1,38 +1,46 @@
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use regex::Regex;
 
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    grid::Grid,
+    solver::Answer,
+};
+
 lazy_static::lazy_static! {
-    static ref TILE: Regex = Regex::new(r#"Tile (\d+):"#).unwrap();
+    static ref TILE: Regex = Regex::new(r#"^Tile (\d+):$"#).unwrap();
 }
 
+const SEA_MONSTER: [&str; 3] = [
+    "                  # ",
+    "#    ##    ##    ###",
+    " #  #  #  #  #  #   ",
+];
+
 #[derive(Debug)]
 struct Tile {
     id: u64,
     borders: [u64; 4],
+    image: Image,
 }
 
-// INCOMPLETE: Skipping part 2 for now
-/// This time the tile can be represented as an 8x8 bitboard
-struct StrippedTile(u64);
+/// A square picture, `true` means `#`
+type Image = Grid<bool>;
 
-impl StrippedTile {
-    fn flip_vert(&mut self) {
-        self.0 = self.0 ^ 56;
-    }
+fn top(image: &Image) -> Vec<bool> {
+    image.row(0).to_vec()
+}
 
-    fn flip_hori(&mut self) {
-        self.0 = self.0 ^ 7;
-    }
+fn bottom(image: &Image) -> Vec<bool> {
+    image.row(image.height() - 1).to_vec()
+}
 
-    fn rotate_clockwise(&mut self) {
-        self.0 = (((self.0 >> 3) | (self.0 << 3)) & 63) ^ 56;
-        self.0 = ((self.0 * 0x20800000) >> 26) ^ 56;
-    }
+fn left(image: &Image) -> Vec<bool> {
+    image.column(0)
+}
 
-    fn rotate_counterclockwise(&mut self) {
-        self.0 = (((self.0 >> 3) | (self.0 << 3)) & 63) ^ 7;
-        self.0 = ((self.0 * 0x20800000) >> 26) ^ 7;
-    }
+fn right(image: &Image) -> Vec<bool> {
+    image.column(image.width() - 1)
 }
 
 impl Tile {
@@ -48,55 +56,169 @@ impl Tile {
         res
     }
 
+    /// Encodes a border so that it's the same regardless of the tile's orientation
+    fn border_key(border: &[bool]) -> u64 {
+        let bord = border.iter().fold(0, |acc, &b| (acc << 1) | b as u64);
+        bord.min(Tile::flip(bord))
+    }
+
     fn parse_blob(id: u64, lines: Vec<&str>) -> Tile {
-        let mut borders = [0; 4];
-        let to_parse: [Vec<char>; 4] = [
-            lines[0].chars().collect(),
-            lines.last().unwrap().chars().collect(),
-            lines.iter().map(|l| l.chars().next().unwrap()).collect(),
-            lines.iter().map(|l| l.chars().last().unwrap()).collect(),
+        let image = Grid::from_rows(
+            lines
+                .iter()
+                .map(|l| l.chars().map(|c| c == '#').collect())
+                .collect(),
+        )
+        .unwrap();
+        let borders = [
+            Tile::border_key(&top(&image)),
+            Tile::border_key(&bottom(&image)),
+            Tile::border_key(&left(&image)),
+            Tile::border_key(&right(&image)),
         ];
-        for (i, border) in to_parse.iter().enumerate() {
-            let mut bord = 0;
-            for c in border {
-                bord <<= 1;
-                bord |= {
-                    if *c == '#' {
-                        1
-                    } else {
-                        0
-                    }
-                };
+        Tile { id, borders, image }
+    }
+}
+
+/// Maps each border to the indices of the tiles that have it
+fn edge_map(tiles: &[Tile]) -> FxHashMap<u64, Vec<usize>> {
+    let mut edges = FxHashMap::default();
+    for (i, tile) in tiles.iter().enumerate() {
+        for edge in &tile.borders {
+            edges.entry(*edge).or_insert_with(Vec::new).push(i);
+        }
+    }
+    edges
+}
+
+/// Places every tile and stitches the (stripped) tiles together.
+/// `None` if the tiles can't be laid out in a square where every shared border matches.
+fn assemble(tiles: &[Tile]) -> Option<Image> {
+    let side = (0..=tiles.len()).find(|&side| side * side >= tiles.len())?;
+    if side * side != tiles.len() {
+        return None;
+    }
+    let edges = edge_map(tiles);
+    let unmatched = |border: &[bool]| {
+        edges
+            .get(&Tile::border_key(border))
+            .is_some_and(|tiles| tiles.len() == 1)
+    };
+    // A lone tile has all four borders unmatched
+    let corner = tiles
+        .iter()
+        .position(|tile| tile.borders.iter().filter(|e| edges[e].len() == 1).count() >= 2)?;
+    let mut used = vec![false; tiles.len()];
+    used[corner] = true;
+    // Orient the first corner so that its unmatched borders face outwards
+    let mut placed = vec![tiles[corner]
+        .image
+        .orientations()
+        .into_iter()
+        .find(|o| unmatched(&top(o)) && unmatched(&left(o)))?];
+    for idx in 1..side * side {
+        let x = idx % side;
+        let above = idx.checked_sub(side).map(|i| bottom(&placed[i]));
+        // Match the left neighbour, or the one above for the first column
+        let border = if x > 0 {
+            right(&placed[idx - 1])
+        } else {
+            above.clone()?
+        };
+        let next = *edges
+            .get(&Tile::border_key(&border))?
+            .iter()
+            .find(|&&i| !used[i])?;
+        used[next] = true;
+        let image = tiles[next].image.orientations().into_iter().find(|o| {
+            if x > 0 {
+                border == left(o) && above.as_ref().is_none_or(|above| *above == top(o))
+            } else {
+                border == top(o)
+            }
+        })?;
+        placed.push(image);
+    }
+    // Remove the borders, then stitch the tiles together
+    let tile_size = placed[0].width() - 2;
+    let mut res = vec![vec![]; side * tile_size];
+    for (idx, image) in placed.iter().enumerate() {
+        let stripped = image.crop(1, 1, tile_size, tile_size);
+        for (row, line) in stripped.rows().enumerate() {
+            res[(idx / side) * tile_size + row].extend_from_slice(line);
+        }
+    }
+    Grid::from_rows(res)
+}
+
+/// Counts the `#` that aren't part of a sea monster
+fn water_roughness(image: &Image) -> usize {
+    let monster: Vec<(usize, usize)> = SEA_MONSTER
+        .iter()
+        .enumerate()
+        .flat_map(|(y, line)| {
+            line.chars()
+                .enumerate()
+                .filter(|c| c.1 == '#')
+                .map(move |(x, _)| (x, y))
+        })
+        .collect();
+    let (width, height) = (SEA_MONSTER[0].len(), SEA_MONSTER.len());
+    for orientation in image.orientations() {
+        if orientation.width() < width || orientation.height() < height {
+            // Too small for a single monster
+            continue;
+        }
+        let mut monster_cells = FxHashSet::default();
+        for y in 0..=orientation.height() - height {
+            for x in 0..=orientation.width() - width {
+                if monster.iter().all(|(dx, dy)| orientation[(x + dx, y + dy)]) {
+                    monster_cells.extend(monster.iter().map(|(dx, dy)| (x + dx, y + dy)));
+                }
             }
-            borders[i] = bord.min(Tile::flip(bord));
         }
-        Tile { id, borders }
+        if !monster_cells.is_empty() {
+            return orientation.count(|&b| b) - monster_cells.len();
+        }
     }
+    image.count(|&b| b)
 }
 
-fn parse_input(input: &str) -> Vec<Tile> {
+fn parse_input(input: &str) -> ParseResult<Vec<Tile>> {
+    let src = Source::new(20, input);
     input
         .split("\n\n")
         .map(|p| {
             let mut lines = p.lines();
-            let title = lines.next().unwrap();
-            let id = TILE.captures(title).unwrap()[1].parse().unwrap();
-            let other = lines.collect();
-            Tile::parse_blob(id, other)
+            let title = lines
+                .next()
+                .ok_or_else(|| src.error(p, "expected `Tile <id>:`"))?;
+            let caps = TILE
+                .captures(title)
+                .ok_or_else(|| src.error(title, "expected `Tile <id>:`"))?;
+            let id = src.parse(caps.get(1).unwrap().as_str())?;
+            let other: Vec<&str> = lines.collect();
+            if other.len() != 10 {
+                return Err(src.error(p, "expected 10 rows"));
+            }
+            for line in &other {
+                if line.chars().count() != 10 {
+                    return Err(src.error(line, "expected 10 columns"));
+                }
+                if let Some((pos, c)) = line.char_indices().find(|&(_, c)| c != '.' && c != '#') {
+                    return Err(src.error(&line[pos..pos + c.len_utf8()], "expected `.` or `#`"));
+                }
+            }
+            Ok(Tile::parse_blob(id, other))
         })
         .collect()
 }
 
 #[aoc(day20, part1)]
-fn part1(input: &str) -> u64 {
-    let tiles = parse_input(input);
-    let mut edges = FxHashMap::default();
-    for tile in &tiles {
-        for edge in &tile.borders {
-            edges.entry(*edge).or_insert(vec![]).push(tile.id);
-        }
-    }
-    tiles
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let tiles = parse_input(input)?;
+    let edges = edge_map(&tiles);
+    Ok(tiles
         .iter()
         .filter(|tile| {
             tile.borders
@@ -106,5 +228,174 @@ fn part1(input: &str) -> u64 {
                 == 2
         })
         .map(|tile| tile.id)
-        .product()
+        .product::<u64>()
+        .into())
+}
+
+#[aoc(day20, part2)]
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let tiles = parse_input(input)?;
+    Ok(assemble(&tiles).map(|image| water_roughness(&image)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Tile 2311:
+..##.#..#.
+##..#.....
+#...##..#.
+####.#...#
+##.##.###.
+##...#.###
+.#.#.#..##
+..#....#..
+###...#.#.
+..###..###
+
+Tile 1951:
+#.##...##.
+#.####...#
+.....#..##
+#...######
+.##.#....#
+.###.#####
+###.##.##.
+.###....#.
+..#.#..#.#
+#...##.#..
+
+Tile 1171:
+####...##.
+#..##.#..#
+##.#..#.#.
+.###.####.
+..###.####
+.##....##.
+.#...####.
+#.##.####.
+####..#...
+.....##...
+
+Tile 1427:
+###.##.#..
+.#..#.##..
+.#.##.#..#
+#.#.#.##.#
+....#...##
+...##..##.
+...#.#####
+.#.####.#.
+..#..###.#
+..##.#..#.
+
+Tile 1489:
+##.#.#....
+..##...#..
+.##..##...
+..#...#...
+#####...#.
+#..#.#.#.#
+...#.#.#..
+##.#...##.
+..##.##.##
+###.##.#..
+
+Tile 2473:
+#....####.
+#..#.##...
+#.##..#...
+######.#.#
+.#...#.#.#
+.#########
+.###.#..#.
+########.#
+##...##.#.
+..###.#.#.
+
+Tile 2971:
+..#.#....#
+#...###...
+#.#.###...
+##.##..#..
+.#####..##
+.#..####.#
+#..#.#..#.
+..####.###
+..#.#.###.
+...#.#.#.#
+
+Tile 2729:
+...#.#.#.#
+####.#....
+..#.#.....
+....#..#.#
+.##..##.#.
+.#.####...
+####.#.#..
+##.####...
+##..#.##..
+#.##...##.
+
+Tile 3079:
+#.#.#####.
+.#..######
+..#.......
+######....
+####.#..#.
+.#...#.##.
+#.#####.##
+..#.###...
+..#.......
+..#.###...";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(part1(EXAMPLE), Ok(Answer::Number(20899048083289)));
+    }
+
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(EXAMPLE), Ok(Answer::Number(273)));
+    }
+
+    #[test]
+    fn invalid_tiles() {
+        let err = part1("").unwrap_err();
+        assert_eq!(err.reason, "expected `Tile <id>:`");
+        assert!(part1("Tile 1:\n#.#\n\n\n").is_err());
+        let rows = "é.........\n".to_string() + &"..........\n".repeat(9);
+        let err = part1(&format!("Tile 1:\n{}", rows)).unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (2, 1, "é"));
+    }
+
+    #[test]
+    fn single_tile() {
+        // No room for a monster, so every `#` inside the borders is rough water
+        let tile = EXAMPLE.split("\n\n").next().unwrap();
+        assert_eq!(part1(tile), Ok(Answer::Number(1)));
+        assert_eq!(part2(tile), Ok(Answer::Number(29)));
+    }
+
+    #[test]
+    fn unfit_tiles() {
+        // Four tiles with no border in common
+        let tiles: Vec<String> = (1..=4)
+            .map(|id| {
+                let rows: Vec<String> = (0..10)
+                    .map(|y| {
+                        (0..10)
+                            .map(|x| if (x * y + id) % 3 == 0 { '#' } else { '.' })
+                            .collect()
+                    })
+                    .collect();
+                format!("Tile {}:\n{}", id, rows.join("\n"))
+            })
+            .collect();
+        assert_eq!(part2(&tiles.join("\n\n")), Ok(Answer::NoSolution));
+        // Not a square number of tiles
+        let tiles: Vec<&str> = EXAMPLE.split("\n\n").take(2).collect();
+        assert_eq!(part2(&tiles.join("\n\n")), Ok(Answer::NoSolution));
+    }
 }
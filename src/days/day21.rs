@@ -1,8 +1,14 @@
 use fxhash::FxHashMap;
 use regex::Regex;
 
+use crate::{
+    constraint::{self, SolveError},
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 lazy_static::lazy_static! {
-    static ref REGEX: Regex = Regex::new(r#"(.+) \(contains (.+)\)"#).unwrap();
+    static ref REGEX: Regex = Regex::new(r#"^(.+) \(contains (.+)\)$"#).unwrap();
 }
 
 struct Food {
@@ -10,7 +16,7 @@ struct Food {
     allergens: Vec<String>,
 }
 
-fn calc_allergens(foods: &[Food]) -> Vec<(String, String)> {
+fn calc_allergens(foods: &[Food]) -> Result<Vec<(String, &str)>, SolveError<&str>> {
     let mut allergens = FxHashMap::default();
     for food in foods {
         for allergen in &food.allergens {
@@ -24,63 +30,74 @@ fn calc_allergens(foods: &[Food]) -> Vec<(String, String)> {
     for (allergen, ingredients) in allergens {
         let mut to_assign: Vec<_> = ingredients
             .iter()
-            .map(|v| v.iter())
-            .flatten()
+            .flat_map(|v| v.iter())
             .filter(|i| ingredients.iter().all(|v| v.contains(i)))
             .collect();
         to_assign.sort();
         to_assign.dedup();
-        res.push((allergen.to_string(), to_assign));
+        res.push((allergen.as_str(), to_assign));
     }
     // Day 16 flashbacks
-    crate::reduce_possibilities(&mut res);
-    res.into_iter()
-        .map(|(k, v)| (k, v.first().map(|s| s.to_string()).unwrap()))
-        .collect()
+    Ok(constraint::solve(&res)?
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.as_str()))
+        .collect())
 }
 
-fn parse_input(input: &str) -> Vec<Food> {
+fn parse_input(input: &str) -> ParseResult<Vec<Food>> {
+    let src = Source::new(21, input);
     input
         .lines()
         .map(|l| {
-            let caps = REGEX.captures(l).unwrap();
-            Food {
+            let caps = REGEX
+                .captures(l)
+                .ok_or_else(|| src.error(l, "expected `<ingredients> (contains <allergens>)`"))?;
+            Ok(Food {
                 ingredients: caps[1].split(' ').map(|s| s.to_string()).collect(),
                 allergens: caps[2].split(", ").map(|s| s.to_string()).collect(),
-            }
+            })
         })
         .collect()
 }
 
 #[aoc(day21, part1)]
-fn part1(input: &str) -> usize {
-    let foods = parse_input(input);
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let foods = parse_input(input)?;
     // The ingredients that are known to contain allergens
-    let ingredients: Vec<String> = calc_allergens(&foods).into_iter().map(|(_, i)| i).collect();
-    foods
+    let ingredients: Vec<&str> = match calc_allergens(&foods) {
+        Ok(allergens) => allergens.into_iter().map(|(_, i)| i).collect(),
+        Err(_) => return Ok(Answer::NoSolution),
+    };
+    Ok(foods
         .iter()
         .map(|f| {
             f.ingredients
                 .iter()
-                .filter(|i| !ingredients.contains(i))
+                .filter(|i| !ingredients.contains(&i.as_str()))
                 .count()
         })
-        .sum()
+        .sum::<usize>()
+        .into())
 }
 
 #[aoc(day21, part2)]
-fn part2(input: &str) -> String {
-    let foods = parse_input(input);
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let foods = parse_input(input)?;
     // The ingredients that are known to contain allergens (Thank God I designed this with a possible Part 2 in mind!)
-    let mut ingredients = calc_allergens(&foods);
+    let mut ingredients = match calc_allergens(&foods) {
+        Ok(ingredients) => ingredients,
+        Err(_) => return Ok(Answer::NoSolution),
+    };
+    if ingredients.is_empty() {
+        // No allergens, so no canonical list either
+        return Ok(Answer::NoSolution);
+    }
     ingredients.sort_by_key(|(allergen, _)| allergen.clone());
-    ingredients
+    let ingredients: Vec<&str> = ingredients
         .into_iter()
         .map(|(_, ingredient)| ingredient)
-        .fold(String::new(), |a, b| a + "," + &b)
-        .strip_prefix(',')
-        .unwrap()
-        .to_string()
+        .collect();
+    Ok(ingredients.join(",").into())
 }
 
 #[cfg(test)]
@@ -93,7 +110,7 @@ mod tests {
 trh fvjkl sbzzf mxmxvkd (contains dairy)
 sqjhc fvjkl (contains soy)
 sqjhc mxmxvkd sbzzf (contains fish)";
-        assert_eq!(part1(input), 5);
+        assert_eq!(part1(input), Ok(Answer::Number(5)));
     }
 
     #[test]
@@ -102,6 +119,16 @@ sqjhc mxmxvkd sbzzf (contains fish)";
 trh fvjkl sbzzf mxmxvkd (contains dairy)
 sqjhc fvjkl (contains soy)
 sqjhc mxmxvkd sbzzf (contains fish)";
-        assert_eq!(&part2(input), "mxmxvkd,sqjhc,fvjkl");
+        assert_eq!(part2(input), Ok(Answer::from("mxmxvkd,sqjhc,fvjkl")));
+    }
+
+    #[test]
+    fn no_solution() {
+        // Both allergens can only be in the same ingredient
+        let input = "mxmxvkd (contains dairy, fish)";
+        assert_eq!(part1(input), Ok(Answer::NoSolution));
+        assert_eq!(part2(input), Ok(Answer::NoSolution));
+        assert_eq!(part1(""), Ok(Answer::Number(0)));
+        assert_eq!(part2(""), Ok(Answer::NoSolution));
     }
 }
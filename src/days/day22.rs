@@ -2,6 +2,11 @@ use std::collections::VecDeque;
 
 use fxhash::FxHashSet;
 
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 struct Game {
     player1: VecDeque<u8>,
     player2: VecDeque<u8>,
@@ -160,37 +165,33 @@ impl Game {
     }
 }
 
-fn parse_input(input: &str) -> Game {
+fn parse_deck(deck: &str, src: &Source) -> ParseResult<VecDeque<u8>> {
+    deck.lines().skip(1).map(|l| src.parse(l)).collect()
+}
+
+fn parse_input(input: &str) -> ParseResult<Game> {
+    let src = Source::new(22, input);
     let mut parts = input.split("\n\n");
-    let player1: VecDeque<_> = parts
-        .next()
-        .unwrap()
-        .lines()
-        .skip(1)
-        .map(|l| l.parse().unwrap())
-        .collect();
-    let player2: VecDeque<_> = parts
-        .next()
-        .unwrap()
-        .lines()
-        .skip(1)
-        .map(|l| l.parse().unwrap())
-        .collect();
-    Game {
+    let player1 = parse_deck(parts.next().unwrap(), &src)?;
+    let player2 = parse_deck(src.expect(parts.next(), input, "player 2's deck")?, &src)?;
+    if player1.is_empty() || player2.is_empty() {
+        return Err(src.error(input, "both players need at least one card"));
+    }
+    Ok(Game {
         needed: player1.len() + player2.len(),
         player1,
         player2,
-    }
+    })
 }
 
 #[aoc(day22, part1)]
-fn part1(input: &str) -> usize {
-    parse_input(input).play()
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    Ok(parse_input(input)?.play().into())
 }
 
 #[aoc(day22, part2)]
-fn part2(input: &str) -> usize {
-    parse_input(input).play_recursive()
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    Ok(parse_input(input)?.play_recursive().into())
 }
 
 #[cfg(test)]
@@ -212,7 +213,7 @@ Player 2:
 4
 7
 10";
-        assert_eq!(part1(input), 306);
+        assert_eq!(part1(input), Ok(Answer::Number(306)));
     }
 
     #[test]
@@ -230,6 +231,6 @@ Player 2:
 4
 7
 10";
-        assert_eq!(part2(input), 291);
+        assert_eq!(part2(input), Ok(Answer::Number(291)));
     }
 }
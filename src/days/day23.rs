@@ -1,3 +1,8 @@
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 struct Circle {
     neighbours: Vec<usize>,
     current: usize,
@@ -5,11 +10,21 @@ struct Circle {
 }
 
 impl Circle {
-    fn parse(input: &str, extend_to: usize) -> Circle {
+    fn parse(input: &str, extend_to: usize) -> ParseResult<Circle> {
+        let source = Source::new(23, input);
         let src: Vec<usize> = input
-            .chars()
-            .map(|c| c.to_digit(10).unwrap() as usize)
-            .collect();
+            .char_indices()
+            .map(|(i, c)| match c.to_digit(10) {
+                Some(d) if d > 0 && !input[..i].contains(c) => Ok(d as usize),
+                _ => Err(source.error(
+                    &input[i..i + c.len_utf8()],
+                    "expected a unique cup label from 1 to 9",
+                )),
+            })
+            .collect::<ParseResult<_>>()?;
+        if src.len() != 9 {
+            return Err(source.error(input, "expected 9 cups"));
+        }
         let mut neighbours = Vec::with_capacity(extend_to + 1);
         for x in 0..extend_to + 1 {
             neighbours.push(x + 1);
@@ -28,11 +43,11 @@ impl Circle {
             let idx = neighbours.len() - 1;
             neighbours[idx] = src[0];
         }
-        Circle {
+        Ok(Circle {
             neighbours,
             current: src[0],
             len: extend_to,
-        }
+        })
     }
 
     fn step(&mut self) {
@@ -71,21 +86,21 @@ impl Circle {
 }
 
 #[aoc(day23, part1)]
-fn part1(input: &str) -> String {
-    let mut circle = Circle::parse(input, 9);
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let mut circle = Circle::parse(input, 9)?;
     for _ in 0..100 {
         circle.step();
     }
-    circle.display()
+    Ok(circle.display().into())
 }
 
 #[aoc(day23, part2)]
-fn part2(input: &str) -> u64 {
-    let mut circle = Circle::parse(input, 1e6 as usize);
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let mut circle = Circle::parse(input, 1e6 as usize)?;
     for _ in 0..(1e7 as usize) {
         circle.step()
     }
-    circle.neighbours[1] as u64 * circle.neighbours[circle.neighbours[1]] as u64
+    Ok((circle.neighbours[1] as u64 * circle.neighbours[circle.neighbours[1]] as u64).into())
 }
 
 #[cfg(test)]
@@ -94,11 +109,11 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(&part1("389125467"), "67384529");
+        assert_eq!(part1("389125467"), Ok(Answer::from("67384529")));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2("389125467"), 149245887792);
+        assert_eq!(part2("389125467"), Ok(Answer::Number(149245887792)));
     }
 }
@@ -1,15 +1,14 @@
-use fxhash::{FxHashMap, FxHashSet};
-use strum::IntoEnumIterator;
+use fxhash::FxHashSet;
 
-type Tile = (i32, i32);
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    life::{Hex, Life, Rule},
+    solver::Answer,
+};
 
-struct Grid {
-    to_enable: Vec<Vec<Direction>>,
-    states: FxHashMap<Tile, bool>,
-    blacks: FxHashSet<Tile>,
-}
+type Tile = (i32, i32);
 
-#[derive(Debug, strum::EnumIter)]
+#[derive(Debug)]
 enum Direction {
     NorthEast,
     NorthWest,
@@ -20,27 +19,33 @@ enum Direction {
 }
 
 impl Direction {
-    fn all(input: &str) -> Vec<Direction> {
-        let mut chars = input.chars();
+    fn all(input: &str, src: &Source) -> ParseResult<Vec<Direction>> {
+        let mut chars = input.char_indices();
         let mut res = Vec::with_capacity(input.len());
-        while let Some(ch) = chars.next() {
+        let invalid = |i: usize, c: char| {
+            src.error(
+                &input[i..i + c.len_utf8()],
+                "expected `e`, `w`, `se`, `sw`, `ne` or `nw`",
+            )
+        };
+        while let Some((i, ch)) = chars.next() {
             match ch {
                 'e' => res.push(Direction::East),
                 'w' => res.push(Direction::West),
-                's' => match chars.next().unwrap() {
-                    'e' => res.push(Direction::SouthEast),
-                    'w' => res.push(Direction::SouthWest),
-                    c => panic!("Invalid direction {}", c),
-                },
-                'n' => match chars.next().unwrap() {
-                    'e' => res.push(Direction::NorthEast),
-                    'w' => res.push(Direction::NorthWest),
-                    c => panic!("Invalid direction {}", c),
-                },
-                c => panic!("Invalid direction {}", c),
+                's' | 'n' => {
+                    let (j, next) = chars.next().ok_or_else(|| invalid(i, ch))?;
+                    res.push(match (ch, next) {
+                        ('s', 'e') => Direction::SouthEast,
+                        ('s', 'w') => Direction::SouthWest,
+                        ('n', 'e') => Direction::NorthEast,
+                        ('n', 'w') => Direction::NorthWest,
+                        _ => return Err(invalid(j, next)),
+                    })
+                }
+                c => return Err(invalid(i, c)),
             }
         }
-        res
+        Ok(res)
     }
 
     fn step(&self) -> (i32, i32) {
@@ -56,88 +61,43 @@ impl Direction {
     }
 }
 
-impl Grid {
-    fn enable_all(&mut self) {
-        for directions in self.to_enable.drain(..) {
-            let mut tile = (0, 0);
-            directions.iter().for_each(|d| {
-                let step = d.step();
-                tile.0 += step.0;
-                tile.1 += step.1;
-            });
-            *self.states.entry(tile).or_insert(true) ^= true;
-        }
-    }
-
-    fn adjacent_tiles(&self, tile: &Tile, snapshot: &FxHashSet<Tile>) -> Vec<(Tile, bool)> {
-        Direction::iter()
-            .map(|dir| {
-                let step = dir.step();
-                let tile = (tile.0 + step.0, tile.1 + step.1);
-                (tile, !snapshot.contains(&tile))
-            })
-            .collect()
-    }
-
-    fn flip_all(&mut self) {
-        self.blacks = self
-            .states
-            .iter()
-            .filter_map(|(k, &v)| if !v { Some(*k) } else { None })
-            .collect();
-        for _ in 0..100 {
-            let snapshot = self.blacks.clone();
-            let mut possible_whites = vec![];
-            for old in &snapshot {
-                let (white_neighbours, black_neighbours): (Vec<(Tile, bool)>, Vec<(Tile, bool)>) =
-                    self.adjacent_tiles(old, &snapshot)
-                        .iter()
-                        .partition(|(_, v)| *v);
-                if black_neighbours.len() == 0 || black_neighbours.len() > 2 {
-                    self.blacks.remove(old);
-                }
-                possible_whites.extend(white_neighbours.into_iter().map(|(tile, _)| tile));
-            }
-            // See Day 17
-            possible_whites.sort();
-            let mut index = 0;
-            while index < possible_whites.len() as isize - 2 {
-                let idx = index as usize;
-                let src = possible_whites[idx].clone();
-                let mut occurrences = 0;
-                while possible_whites[idx + occurrences] == src {
-                    occurrences += 1;
-                }
-                if occurrences == 2 {
-                    self.blacks.insert(src);
-                }
-                index += occurrences as isize;
-            }
+/// Flips the tile at the end of each path, and returns the black ones
+fn black_tiles(paths: &[Vec<Direction>]) -> FxHashSet<Tile> {
+    let mut blacks = FxHashSet::default();
+    for directions in paths {
+        let mut tile = (0, 0);
+        directions.iter().for_each(|d| {
+            let step = d.step();
+            tile.0 += step.0;
+            tile.1 += step.1;
+        });
+        if !blacks.insert(tile) {
+            blacks.remove(&tile);
         }
     }
+    blacks
 }
 
-fn parse_input(input: &str) -> Grid {
-    Grid {
-        states: FxHashMap::default(),
-        to_enable: input.lines().map(|l| Direction::all(l)).collect(),
-        blacks: FxHashSet::default(),
-    }
+fn parse_input(input: &str) -> ParseResult<Vec<Vec<Direction>>> {
+    let src = Source::new(24, input);
+    input.lines().map(|l| Direction::all(l, &src)).collect()
 }
 
 #[aoc(day24, part1)]
-fn part1(input: &str) -> usize {
-    let mut grid = parse_input(input);
-    grid.enable_all();
-    grid.states.values().filter(|&&v| !v).count()
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    Ok(black_tiles(&parse_input(input)?).len().into())
 }
 
 #[aoc(day24, part2)]
-fn part2(input: &str) -> usize {
-    let mut grid = parse_input(input);
-    grid.enable_all();
-    grid.flip_all();
-    grid.blacks.len()
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    // Black tiles stay black with 1 or 2 black neighbours, white tiles turn black with exactly 2
+    let mut life = Life::new(
+        Hex,
+        Rule::new(&[2], &[1, 2]),
+        black_tiles(&parse_input(input)?),
+    );
+    life.run(100);
+    Ok(life.population().into())
 }
 
 #[cfg(test)]
@@ -166,7 +126,7 @@ nenewswnwewswnenesenwnesewesw
 eneswnwswnwsenenwnwnwwseeswneewsenese
 neswnwewnwnwseenwseesewsenwsweewe
 wseweeenwnesenwwwswnew";
-        assert_eq!(part1(input), 10);
+        assert_eq!(part1(input), Ok(Answer::Number(10)));
     }
 
     #[test]
@@ -191,6 +151,6 @@ nenewswnwewswnenesenwnesewesw
 eneswnwswnwsenenwnwnwwseeswneewsenese
 neswnwewnwnwseenwseesewsenwsweewe
 wseweeenwnesenwwwswnew";
-        assert_eq!(part2(input), 2208);
+        assert_eq!(part2(input), Ok(Answer::Number(2208)));
     }
 }
@@ -1,3 +1,8 @@
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
 fn reverse_subject(subject: usize, pub_key: usize) -> usize {
     let mut i = 0;
     let mut res = 1;
@@ -18,24 +23,30 @@ fn calc_subject(subject: usize, i: usize) -> usize {
     res
 }
 
-fn parse_input(input: &str) -> (usize, usize) {
+fn parse_input(input: &str) -> ParseResult<(usize, usize)> {
+    let src = Source::new(25, input);
     let mut lines = input.lines();
-    (
-        lines.next().unwrap().parse().unwrap(),
-        lines.next().unwrap().parse().unwrap(),
-    )
+    let mut key = |what| {
+        let line = src.expect(lines.next(), input, what)?;
+        match src.parse(line)? {
+            // Anything else would make `reverse_subject` loop forever
+            key if key > 0 && key < 20201227 => Ok(key),
+            _ => Err(src.error(line, "public keys must be between 1 and 20201226")),
+        }
+    };
+    Ok((key("the card's public key")?, key("the door's public key")?))
 }
 
 #[aoc(day25, part1)]
-fn part1(input: &str) -> usize {
-    let (card_pub, door_pub) = parse_input(input);
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let (card_pub, door_pub) = parse_input(input)?;
     let (card_loop, door_loop) = (reverse_subject(7, card_pub), reverse_subject(7, door_pub));
     let (card_enc, door_enc) = (
         calc_subject(door_pub, card_loop),
         calc_subject(card_pub, door_loop),
     );
     assert_eq!(card_enc, door_enc);
-    card_enc
+    Ok(card_enc.into())
 }
 
 #[cfg(test)]
@@ -45,6 +56,6 @@ mod tests {
     #[test]
     fn part1_example() {
         let input = example!(5764801 17807724);
-        assert_eq!(part1(input), 14897079);
+        assert_eq!(part1(input), Ok(Answer::Number(14897079)));
     }
 }
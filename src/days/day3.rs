@@ -1,93 +1,190 @@
-struct Map {
-    src: Vec<Vec<char>>,
-    max_x: usize,
+//! The toboggan ride. Besides the fixed slopes of the puzzle, routes can repeat any sequence of moves,
+//! and every slope within some bounds can be tried to find the one that hits the fewest trees.
+
+use std::ops::RangeInclusive;
+
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    grid::{Grid, Wrap},
+    solver::Answer,
+};
+
+/// `true` is a tree. The map repeats to the right, and ends at the bottom.
+pub type Map = Grid<bool>;
+
+pub fn parse_map(input: &str) -> ParseResult<Map> {
+    Grid::parse(&Source::new(3, input), input, "`.` or `#`", |c| match c {
+        '.' => Some(false),
+        '#' => Some(true),
+        _ => None,
+    })
 }
 
-impl Map {
-    fn parse(input: &str) -> Map {
-        let mut src = vec![];
-        let mut max_x = 0;
-        for line in input.lines() {
-            let lines: Vec<char> = line.trim().chars().collect();
-            if max_x == 0 {
-                max_x = lines.len();
-            }
-            src.push(lines);
+/// A sequence of `(right, down)` moves, repeated until the bottom of the map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route(Vec<(isize, usize)>);
+
+impl Route {
+    /// `None` if no move goes down, since the route would never reach the bottom
+    pub fn new(moves: Vec<(isize, usize)>) -> Option<Route> {
+        if moves.iter().any(|&(_, down)| down > 0) {
+            Some(Route(moves))
+        } else {
+            None
         }
-        Map { src, max_x }
     }
 
-    /// Returns the element at the given position.
-    /// It will automatically overlap (on the right side) and return `None` when the bottom of the map is reached.
-    fn get(&self, x: usize, y: usize) -> Option<&char> {
-        if y >= self.src.len() {
-            return None;
-        }
-        self.src
-            .get(y % self.src.len())
-            .and_then(|v| v.get(x % self.max_x))
+    /// The usual slope: `right` then `down`, over and over. `None` if `down` is 0.
+    pub fn slope(right: isize, down: usize) -> Option<Route> {
+        Route::new(vec![(right, down)])
+    }
+
+    /// Goes down one row at a time, moving `right` columns every `down` rows as evenly as possible.
+    /// `Route::ratio(3, 2)` alternates between 1 and 2 columns to the right. `None` if `down` is 0.
+    pub fn ratio(right: isize, down: usize) -> Option<Route> {
+        let down_i = down as isize;
+        let moves = (0..down_i)
+            .map(|i| (right * (i + 1) / down_i - right * i / down_i, 1))
+            .collect();
+        Route::new(moves)
+    }
+
+    pub fn moves(&self) -> &[(isize, usize)] {
+        &self.0
     }
 }
 
-fn calc_trees(map: &Map, slope: (usize, usize)) -> i32 {
-    let mut last = (slope.0, slope.1);
-    let mut count = 0;
-    while let Some(ch) = map.get(last.0, last.1) {
-        if *ch == '#' {
-            count += 1;
+/// Where a route went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trip {
+    /// Every position after a move (the start isn't included), with `x` inside the map
+    pub path: Vec<(usize, usize)>,
+    pub trees: usize,
+}
+
+/// Follows `route` from the top left corner to the bottom of the map
+pub fn ride(map: &Map, route: &Route) -> Trip {
+    let mut trip = Trip {
+        path: vec![],
+        trees: 0,
+    };
+    let (mut x, mut y) = (0, 0);
+    for &(right, down) in route.0.iter().cycle() {
+        x += right;
+        y += down as isize;
+        match map.get_wrapping(x, y, Wrap::Horizontal) {
+            Some(&tree) => {
+                trip.path
+                    .push((x.rem_euclid(map.width() as isize) as usize, y as usize));
+                trip.trees += tree as usize;
+            }
+            None => break,
         }
-        last.0 += slope.0;
-        last.1 += slope.1;
     }
-    count
+    trip
+}
+
+/// The trees hit by every `(right, down)` slope within the bounds. Slopes that don't go down are skipped.
+pub fn evaluate_slopes(
+    map: &Map,
+    rights: RangeInclusive<isize>,
+    downs: RangeInclusive<usize>,
+) -> Vec<((isize, usize), usize)> {
+    downs
+        .flat_map(|down| rights.clone().map(move |right| (right, down)))
+        .filter_map(|(right, down)| {
+            let route = Route::slope(right, down)?;
+            Some(((right, down), ride(map, &route).trees))
+        })
+        .collect()
+}
+
+/// The slope within the bounds that hits the fewest trees, and how many it hits.
+/// Ties go to the smallest `down`, then the smallest `right`.
+pub fn safest_slope(
+    map: &Map,
+    rights: RangeInclusive<isize>,
+    downs: RangeInclusive<usize>,
+) -> Option<((isize, usize), usize)> {
+    evaluate_slopes(map, rights, downs)
+        .into_iter()
+        .min_by_key(|&(_, trees)| trees)
+}
+
+/// The trees hit by one of the puzzle's slopes, which all go down
+fn slope_trees(map: &Map, right: isize, down: usize) -> usize {
+    Route::slope(right, down).map_or(0, |route| ride(map, &route).trees)
 }
 
 #[aoc(day3, part1)]
-fn part1(input: &str) -> i32 {
-    let map = Map::parse(input);
-    calc_trees(&map, (3, 1))
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let map = parse_map(input)?;
+    Ok(slope_trees(&map, 3, 1).into())
 }
 
 #[aoc(day3, part2)]
-fn part2(input: &str) -> u32 {
-    let map = Map::parse(input);
-    [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)]
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let map = parse_map(input)?;
+    Ok([(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)]
         .iter()
-        .fold(1u32, |v, slope| v * calc_trees(&map, *slope) as u32)
+        .map(|&(right, down)| slope_trees(&map, right, down))
+        .product::<usize>()
+        .into())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    const EXAMPLE: &str = "..##.......
+#...#...#..
+.#....#..#.
+..#.#...#.#
+.#...##..#.
+..#.##.....
+.#.#.#....#
+.#........#
+#.##...#...
+#...##....#
+.#..#...#.#";
+
     #[test]
     fn part1_example() {
-        let example = "..##.......
-        #...#...#..
-        .#....#..#.
-        ..#.#...#.#
-        .#...##..#.
-        ..#.##.....
-        .#.#.#....#
-        .#........#
-        #.##...#...
-        #...##....#
-        .#..#...#.#";
-        assert_eq!(part1(example), 7);
+        assert_eq!(part1(EXAMPLE), Ok(Answer::Number(7)));
     }
 
     #[test]
     fn part2_example() {
-        let example = "..##.......
-        #...#...#..
-        .#....#..#.
-        ..#.#...#.#
-        .#...##..#.
-        ..#.##.....
-        .#.#.#....#
-        .#........#
-        #.##...#...
-        #...##....#
-        .#..#...#.#";
-        assert_eq!(part2(example), 336);
+        assert_eq!(part2(EXAMPLE), Ok(Answer::Number(336)));
+    }
+
+    #[test]
+    fn routes() {
+        let map = parse_map(EXAMPLE).unwrap();
+        let trip = ride(&map, &Route::slope(3, 1).unwrap());
+        assert_eq!(trip.path.len(), 10);
+        assert_eq!(&trip.path[..4], &[(3, 1), (6, 2), (9, 3), (1, 4)]);
+        assert_eq!(trip.trees, 7);
+        // Going left works too, the map repeats both ways
+        assert_eq!(ride(&map, &Route::slope(-3, 1).unwrap()).path[0], (8, 1));
+        assert_eq!(Route::ratio(3, 2).unwrap().moves(), &[(1, 1), (2, 1)]);
+        let trip = ride(&map, &Route::ratio(3, 2).unwrap());
+        assert_eq!(&trip.path[..3], &[(1, 1), (3, 2), (4, 3)]);
+        assert_eq!(
+            ride(&map, &Route::new(vec![(1, 0), (0, 1)]).unwrap()).path[..2],
+            [(1, 0), (1, 1)]
+        );
+        assert_eq!(Route::ratio(1, 0), None);
+        assert_eq!(Route::new(vec![(1, 0)]), None);
+    }
+
+    #[test]
+    fn safest() {
+        let map = parse_map(EXAMPLE).unwrap();
+        let slopes = evaluate_slopes(&map, 1..=7, 0..=2);
+        assert_eq!(slopes.len(), 14);
+        assert!(slopes.contains(&((3, 1), 7)));
+        assert!(slopes.contains(&((1, 2), 2)));
+        assert_eq!(safest_slope(&map, 0..=10, 1..=3), Some(((5, 2), 0)));
     }
 }
@@ -1,121 +1,322 @@
+//! Passport validation, driven by a [`Schema`] declaring which fields exist and what their values look like.
+//! Checking a passport lists every field that's wrong, and why.
+
+use std::{fmt, ops::RangeInclusive};
+
 use regex::Regex;
 
-const REQUIRED_VARIANTS: [&str; 7] = [
-    "BirthYear",
-    "IssueYear",
-    "ExpiryYear",
-    "Height",
-    "HairColor",
-    "EyeColor",
-    "PassportId",
-];
-lazy_static::lazy_static! {
-    static ref HEIGHT_REGEX: Regex = Regex::new(r#"^(\d+)(cm|in)$"#).unwrap();
-    static ref HAIR_COLOR_REGEX: Regex = Regex::new(r#"^#[0-9a-f]{6}$"#).unwrap();
-    static ref PASSPORT_ID_REGEX: Regex = Regex::new(r#"^[0-9]{9}$"#).unwrap();
-}
-
-struct Passport<'a>(Vec<Entry<'a>>);
-
-#[derive(strum::IntoStaticStr)]
-enum Entry<'a> {
-    BirthYear(u16),
-    IssueYear(u16),
-    ExpiryYear(u16),
-    Height(&'a str),
-    HairColor(&'a str),
-    EyeColor(&'a str),
-    PassportId(&'a str),
-    CountryId,
-}
-
-impl<'a> Entry<'a> {
-    fn from(input: (&'a str, &'a str)) -> Entry<'a> {
-        match input.0 {
-            "byr" => Entry::BirthYear(input.1.parse().unwrap()),
-            "iyr" => Entry::IssueYear(input.1.parse().unwrap()),
-            "eyr" => Entry::ExpiryYear(input.1.parse().unwrap()),
-            "hgt" => Entry::Height(input.1),
-            "hcl" => Entry::HairColor(input.1),
-            "ecl" => Entry::EyeColor(input.1),
-            "pid" => Entry::PassportId(input.1),
-            "cid" => Entry::CountryId,
-            f => panic!("Invalid field {}", f),
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
+/// What a field's value must look like
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Any,
+    /// A number in the range
+    Number(RangeInclusive<u32>),
+    /// A number followed by one of the units, each with its own range
+    Measure(Vec<(String, RangeInclusive<u32>)>),
+    /// Matches the whole value
+    Pattern(Regex),
+    OneOf(Vec<String>),
+}
+
+impl Rule {
+    /// A pattern rule. The regex must be valid, and is anchored to the whole value.
+    pub fn pattern(regex: &str) -> Rule {
+        Rule::Pattern(Regex::new(&format!("^(?:{})$", regex)).unwrap())
+    }
+
+    pub fn measure(units: &[(&str, RangeInclusive<u32>)]) -> Rule {
+        Rule::Measure(
+            units
+                .iter()
+                .map(|(unit, range)| (unit.to_string(), range.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn one_of(values: &[&str]) -> Rule {
+        Rule::OneOf(values.iter().map(|v| v.to_string()).collect())
+    }
+
+    fn check_number(value: &str, range: &RangeInclusive<u32>) -> Result<(), Problem> {
+        let n: u32 = value
+            .parse()
+            .map_err(|_| Problem::NotANumber(value.to_string()))?;
+        if range.contains(&n) {
+            Ok(())
+        } else {
+            Err(Problem::OutOfRange(n, range.clone()))
         }
     }
 
-    #[inline]
-    fn validate(&self) -> bool {
+    pub fn check(&self, value: &str) -> Result<(), Problem> {
         match self {
-            Entry::BirthYear(y) => (1920..=2002).contains(y),
-            Entry::IssueYear(y) => (2010..=2020).contains(y),
-            Entry::ExpiryYear(y) => (2020..=2030).contains(y),
-            Entry::Height(h) => {
-                let caps = HEIGHT_REGEX.captures(h);
-                match caps {
-                    Some(caps) => {
-                        let num: u16 = caps[1].parse().unwrap();
-                        let unit = &caps[2];
-                        match (unit, num) {
-                            ("cm", 150..=193) | ("in", 59..=76) => true,
-                            _ => false,
-                        }
-                    }
-                    None => false,
-                }
+            Rule::Any => Ok(()),
+            Rule::Number(range) => Rule::check_number(value, range),
+            Rule::Measure(units) => {
+                let split = value
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(value.len());
+                let (number, unit) = value.split_at(split);
+                let (_, range) = units
+                    .iter()
+                    .find(|(u, _)| u == unit)
+                    .ok_or_else(|| Problem::UnknownUnit(unit.to_string()))?;
+                Rule::check_number(number, range)
             }
-            Entry::HairColor(c) => HAIR_COLOR_REGEX.is_match(c),
-            Entry::EyeColor(c) => ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"].contains(c),
-            Entry::PassportId(i) => PASSPORT_ID_REGEX.is_match(i),
-            Entry::CountryId => true,
+            Rule::Pattern(regex) if regex.is_match(value) => Ok(()),
+            Rule::Pattern(regex) => Err(Problem::NoMatch(regex.as_str().to_string())),
+            Rule::OneOf(values) if values.iter().any(|v| v == value) => Ok(()),
+            Rule::OneOf(values) => Err(Problem::NotOneOf(values.clone())),
         }
     }
 }
 
+/// Why a field is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    Missing,
+    /// The schema doesn't know the field
+    Unknown,
+    Duplicate,
+    NotANumber(String),
+    OutOfRange(u32, RangeInclusive<u32>),
+    UnknownUnit(String),
+    /// The value doesn't match the pattern
+    NoMatch(String),
+    NotOneOf(Vec<String>),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Missing => write!(f, "missing"),
+            Problem::Unknown => write!(f, "unknown field"),
+            Problem::Duplicate => write!(f, "given more than once"),
+            Problem::NotANumber(s) => write!(f, "{:?} isn't a number", s),
+            Problem::OutOfRange(n, range) => write!(
+                f,
+                "{} isn't between {} and {}",
+                n,
+                range.start(),
+                range.end()
+            ),
+            Problem::UnknownUnit(unit) => write!(f, "unknown unit {:?}", unit),
+            Problem::NoMatch(regex) => write!(f, "doesn't match `{}`", regex),
+            Problem::NotOneOf(values) => write!(f, "expected one of {}", values.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub required: bool,
+    pub rule: Rule,
+}
+
+/// The fields a passport can have. Built field by field:
+///
+/// ```
+/// # use aoc_2020::days::day4::{Rule, Schema};
+/// let schema = Schema::new()
+///     .required("byr", Rule::Number(1920..=2002))
+///     .required("hgt", Rule::measure(&[("cm", 150..=193), ("in", 59..=76)]))
+///     .optional("cid", Rule::Any);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    fields: Vec<Field>,
+    /// Whether fields outside the schema, and fields given more than once, are fine
+    allow_extra: bool,
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    pub fn field(mut self, name: &str, required: bool, rule: Rule) -> Schema {
+        self.fields.push(Field {
+            name: name.to_string(),
+            required,
+            rule,
+        });
+        self
+    }
+
+    pub fn required(self, name: &str, rule: Rule) -> Schema {
+        self.field(name, true, rule)
+    }
+
+    pub fn optional(self, name: &str, rule: Rule) -> Schema {
+        self.field(name, false, rule)
+    }
+
+    /// Accepts unknown fields, and checks the first value of fields given more than once
+    pub fn allow_extra(mut self) -> Schema {
+        self.allow_extra = true;
+        self
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// The same fields, accepting any value and any extra field: only checks that the required ones are there
+    pub fn presence_only(&self) -> Schema {
+        Schema {
+            fields: self
+                .fields
+                .iter()
+                .map(|f| Field {
+                    rule: Rule::Any,
+                    ..f.clone()
+                })
+                .collect(),
+            allow_extra: true,
+        }
+    }
+
+    /// Every problem with the passport, in the order of the schema then the unknown fields
+    pub fn check(&self, passport: &Passport) -> Report {
+        let mut failures = vec![];
+        for field in &self.fields {
+            let values: Vec<&str> = passport.values(&field.name).collect();
+            let problem = match values[..] {
+                [] if field.required => Some(Problem::Missing),
+                [] => None,
+                [value] => field.rule.check(value).err(),
+                [value, ..] if self.allow_extra => field.rule.check(value).err(),
+                _ => Some(Problem::Duplicate),
+            };
+            if let Some(problem) = problem {
+                failures.push(Failure {
+                    field: field.name.clone(),
+                    value: values.first().map(|v| v.to_string()),
+                    problem,
+                });
+            }
+        }
+        for &(name, value) in &passport.0 {
+            if !self.allow_extra && !self.fields.iter().any(|f| f.name == name) {
+                failures.push(Failure {
+                    field: name.to_string(),
+                    value: Some(value.to_string()),
+                    problem: Problem::Unknown,
+                });
+            }
+        }
+        Report { failures }
+    }
+}
+
+/// The rules of part 2
+pub fn puzzle_schema() -> Schema {
+    Schema::new()
+        .required("byr", Rule::Number(1920..=2002))
+        .required("iyr", Rule::Number(2010..=2020))
+        .required("eyr", Rule::Number(2020..=2030))
+        .required("hgt", Rule::measure(&[("cm", 150..=193), ("in", 59..=76)]))
+        .required("hcl", Rule::pattern("#[0-9a-f]{6}"))
+        .required(
+            "ecl",
+            Rule::one_of(&["amb", "blu", "brn", "gry", "grn", "hzl", "oth"]),
+        )
+        .required("pid", Rule::pattern("[0-9]{9}"))
+        .optional("cid", Rule::Any)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub field: String,
+    /// `None` if the field is missing
+    pub value: Option<String>,
+    pub problem: Problem,
+}
+
+/// The result of checking a passport against a schema
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "valid");
+        }
+        for (i, failure) in self.failures.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {}", failure.field, failure.problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `key:value` pairs of a passport, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passport<'a>(Vec<(&'a str, &'a str)>);
+
 impl<'a> Passport<'a> {
-    fn parse(batch: &'a str, re: &Regex) -> Passport<'a> {
-        let entries = re
-            .split(batch)
+    fn parse(batch: &'a str, src: &Source) -> ParseResult<Passport<'a>> {
+        let fields = batch
+            .split_whitespace()
             .map(|pair| {
-                let mut split = pair.split(':');
-                Entry::from((split.next().unwrap(), split.next().unwrap()))
+                let mut split = pair.splitn(2, ':');
+                let key = split.next().unwrap();
+                let value = src.expect(split.next(), key, "`:` after the field name")?;
+                Ok((key, value))
             })
-            .collect();
-        Passport(entries)
-    }
-
-    fn validate_required(&self) -> bool {
-        let variants: Vec<&'static str> = self.0.iter().map(|e| e.into()).collect();
-        REQUIRED_VARIANTS.iter().all(|k| variants.contains(k))
+            .collect::<ParseResult<_>>()?;
+        Ok(Passport(fields))
     }
 
-    fn validate_valid(&self) -> bool {
-        self.0.iter().all(|e| e.validate())
+    pub fn values<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'a str> + 's {
+        self.0
+            .iter()
+            .filter(move |(k, _)| *k == name)
+            .map(|&(_, v)| v)
     }
 }
 
-fn parse<'a>(input: &'a str) -> Vec<Passport<'a>> {
-    let regex = Regex::new("[\\s\\n]").unwrap();
+/// Passports are separated by blank lines
+pub fn parse_passports(input: &str) -> ParseResult<Vec<Passport<'_>>> {
+    let src = Source::new(4, input);
     input
         .split("\n\n")
-        .map(|batch| Passport::parse(batch, &regex))
+        .map(|batch| Passport::parse(batch, &src))
         .collect()
 }
 
-#[aoc(day4, part1)]
-fn part1(input: &str) -> usize {
-    parse(input)
+fn count_valid(input: &str, schema: &Schema) -> ParseResult<Answer> {
+    Ok(parse_passports(input)?
         .iter()
-        .filter(|p| p.validate_required())
+        .filter(|p| schema.check(p).is_valid())
         .count()
+        .into())
+}
+
+#[aoc(day4, part1)]
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    count_valid(input, &puzzle_schema().presence_only())
 }
 
 #[aoc(day4, part2)]
-fn part2(input: &str) -> usize {
-    parse(input)
-        .iter()
-        .filter(|p| p.validate_valid() && p.validate_required())
-        .count()
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    count_valid(input, &puzzle_schema())
 }
 
 #[cfg(test)]
@@ -137,7 +338,7 @@ hgt:179cm
 
 hcl:#cfa07d eyr:2025 pid:166559648
 iyr:2011 ecl:brn hgt:59in";
-        assert_eq!(part1(input), 2);
+        assert_eq!(part1(input), Ok(Answer::Number(2)));
     }
 
     #[test]
@@ -155,7 +356,7 @@ ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992 cid:277
 hgt:59cm ecl:zzz
 eyr:2038 hcl:74454a iyr:2023
 pid:3556412378 byr:2007";
-        assert_eq!(part2(input), 0);
+        assert_eq!(part2(input), Ok(Answer::Number(0)));
     }
 
     #[test]
@@ -172,6 +373,64 @@ pid:545766238 ecl:hzl
 eyr:2022
 
 iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719";
-        assert_eq!(part2(input), 4);
+        assert_eq!(part2(input), Ok(Answer::Number(4)));
+    }
+
+    #[test]
+    fn unknown_field() {
+        let passports = parse_passports("ecl:gry pid:860033327\nfoo:bar").unwrap();
+        let report = puzzle_schema().check(&passports[0]);
+        assert_eq!(
+            report.failures.last(),
+            Some(&Failure {
+                field: "foo".to_string(),
+                value: Some("bar".to_string()),
+                problem: Problem::Unknown
+            })
+        );
+        // Part 1 only looks for the required fields
+        let report = puzzle_schema().presence_only().check(&passports[0]);
+        assert!(report
+            .failures
+            .iter()
+            .all(|f| f.problem == Problem::Missing));
+        let input = "ecl:gry pid:1 eyr:x hcl:y ecl:z\nbyr:1 iyr:2 hgt:3 foo:bar";
+        assert_eq!(part1(input), Ok(Answer::Number(1)));
+        assert_eq!(part2(input), Ok(Answer::Number(0)));
+        let err = part1("ecl:gry pid\nfoo:bar").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 12));
+    }
+
+    #[test]
+    fn reports() {
+        let input = "hgt:59cm ecl:zzz
+eyr:2038 hcl:74454a iyr:2023 iyr:2020
+pid:3556412378 byr:abc";
+        let passports = parse_passports(input).unwrap();
+        let report = puzzle_schema().check(&passports[0]);
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.to_string(),
+            "byr: \"abc\" isn't a number; iyr: given more than once; \
+             eyr: 2038 isn't between 2020 and 2030; hgt: 59 isn't between 150 and 193; \
+             hcl: doesn't match `^(?:#[0-9a-f]{6})$`; ecl: expected one of amb, blu, brn, gry, grn, hzl, oth; \
+             pid: doesn't match `^(?:[0-9]{9})$`"
+        );
+        let schema = Schema::new()
+            .required("hgt", Rule::measure(&[("m", 1..=3)]))
+            .required("name", Rule::Any);
+        let report = schema.check(&passports[0]);
+        assert_eq!(
+            report.failures[0].problem,
+            Problem::UnknownUnit("cm".to_string())
+        );
+        assert_eq!(
+            (
+                report.failures[1].field.as_str(),
+                &report.failures[1].problem
+            ),
+            ("name", &Problem::Missing)
+        );
+        assert_eq!(report.failures.len(), 2 + 7);
     }
 }
@@ -1,87 +1,193 @@
-struct Seat {
-    row: u8,
-    column: u8,
+//! Boarding passes encode a seat in binary: `F`/`B` pick the front or back half of the rows,
+//! `L`/`R` the left or right half of the columns.
+
+use std::iter;
+
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
+/// How many bits (characters) the row and column take up in a boarding pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub row_bits: u32,
+    pub column_bits: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Seat {
+    pub row: u64,
+    pub column: u64,
+    /// `row * columns + column`
+    pub id: u64,
+}
+
+/// The lowest `bits` bits set
+fn mask(bits: u32) -> u64 {
+    u64::MAX.checked_shr(64 - bits).unwrap_or(0)
 }
 
-impl Seat {
-    // Reuse the allocation for cols and rows
-    fn parse(input: &str, rows: &mut [u8; 128], cols: &mut [u8; 8]) -> Seat {
-        for i in 0..=127 {
-            rows[i] = i as u8
+impl Layout {
+    /// 128 rows and 8 columns
+    pub const PUZZLE: Layout = Layout {
+        row_bits: 7,
+        column_bits: 3,
+    };
+
+    /// Seat ids must fit in a `u64`, so there can be at most 64 bits in total
+    pub fn new(row_bits: u32, column_bits: u32) -> Layout {
+        assert!(row_bits + column_bits <= 64, "too many bits for a seat id");
+        Layout {
+            row_bits,
+            column_bits,
         }
-        for i in 0..=7 {
-            cols[i] = i as u8
+    }
+
+    fn bits(&self) -> u32 {
+        self.row_bits + self.column_bits
+    }
+
+    /// The number of seats in the plane, `None` if it doesn't fit in a `u64`
+    pub fn seats(&self) -> Option<u64> {
+        1u64.checked_shl(self.bits())
+    }
+
+    pub fn seat(&self, id: u64) -> Option<Seat> {
+        if self.seats().is_some_and(|n| id >= n) {
+            return None;
         }
-        let mut rows = (128, &rows[..]);
-        let mut cols = (8, &cols[..]);
-        for ch in input.chars() {
-            match ch {
-                'F' => {
-                    rows.0 /= 2;
-                    rows.1 = &rows.1[0..rows.0];
-                }
-                'B' => {
-                    rows.0 /= 2;
-                    rows.1 = &rows.1[rows.0..(rows.0 * 2)];
-                }
-                'L' => {
-                    cols.0 /= 2;
-                    cols.1 = &cols.1[0..cols.0];
-                }
-                'R' => {
-                    cols.0 /= 2;
-                    cols.1 = &cols.1[cols.0..(cols.0 * 2)];
+        Some(Seat {
+            row: id.checked_shr(self.column_bits).unwrap_or(0),
+            column: id & mask(self.column_bits),
+            id,
+        })
+    }
+
+    /// Decodes a boarding pass. Row and column characters can come in any order.
+    pub fn decode(&self, pass: &str) -> ParseResult<Seat> {
+        self.decode_in(pass, &Source::new(5, pass))
+    }
+
+    fn decode_in(&self, pass: &str, src: &Source) -> ParseResult<Seat> {
+        let (mut row, mut rows) = (0u64, 0);
+        let (mut column, mut columns) = (0u64, 0);
+        for (i, ch) in pass.char_indices() {
+            let (value, count, max) = match ch {
+                'F' | 'B' => (&mut row, &mut rows, self.row_bits),
+                'L' | 'R' => (&mut column, &mut columns, self.column_bits),
+                c => {
+                    return Err(
+                        src.error(&pass[i..i + c.len_utf8()], "expected `F`, `B`, `L` or `R`")
+                    )
                 }
-                c => panic!("Invalid char {}", c),
+            };
+            if *count == max {
+                return Err(src.error(&pass[i..i + 1], "too many characters"));
             }
+            *value = *value << 1 | (ch == 'B' || ch == 'R') as u64;
+            *count += 1;
         }
-        Seat {
-            row: rows.1[0],
-            column: cols.1[0],
+        if rows != self.row_bits || columns != self.column_bits {
+            return Err(src.error(
+                pass,
+                format!(
+                    "expected {} row and {} column characters",
+                    self.row_bits, self.column_bits
+                ),
+            ));
         }
+        Ok(Seat {
+            row,
+            column,
+            id: row.checked_shl(self.column_bits).unwrap_or(0) | column,
+        })
     }
 
-    fn get_id(&self) -> i32 {
-        self.row as i32 * 8 + self.column as i32
+    /// The boarding pass for a seat id, rows first. `None` if the plane is too small.
+    pub fn encode(&self, id: u64) -> Option<String> {
+        let seat = self.seat(id)?;
+        let bits = |value: u64, width: u32, zero: char, one: char| {
+            (0..width)
+                .rev()
+                .map(move |bit| if value >> bit & 1 == 1 { one } else { zero })
+        };
+        Some(
+            bits(seat.row, self.row_bits, 'F', 'B')
+                .chain(bits(seat.column, self.column_bits, 'L', 'R'))
+                .collect(),
+        )
     }
 }
 
-#[aoc(day5, part1)]
-fn part1(input: &str) -> i32 {
-    let mut rows = [0; 128];
-    let mut cols = [0; 8];
-    input
-        .lines()
-        .map(|line| Seat::parse(line, &mut rows, &mut cols).get_id())
-        .max()
-        .unwrap()
+/// The occupied seats of a plane
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plane {
+    pub layout: Layout,
+    /// Sorted seat ids
+    ids: Vec<u64>,
 }
 
-#[aoc(day5, part2)]
-fn part2(input: &str) -> i32 {
-    let mut rows = [0; 128];
-    let mut cols = [0; 8];
-    // Sorted vec + binary search is 20% faster than (default) HashSet contains()
-    let seats = {
-        let mut seats: Vec<i32> = input
+impl Plane {
+    /// Reads one boarding pass per line
+    pub fn parse(layout: Layout, input: &str) -> ParseResult<Plane> {
+        let src = Source::new(5, input);
+        // Sorted vec + binary search is 20% faster than (default) HashSet contains()
+        let mut ids = input
             .lines()
-            .map(|line| Seat::parse(line, &mut rows, &mut cols).get_id())
-            .collect();
-        seats.sort();
-        seats
-    };
-    for row in 0..=127 {
-        for col in 0..=7 {
-            let id = row * 8 + col;
-            if !seats.binary_search(&id).is_err()
-                && seats.binary_search(&(id + 1)).is_ok()
-                && seats.binary_search(&(id - 1)).is_ok()
-            {
-                return id;
-            }
-        }
+            .map(|line| Ok(layout.decode_in(line, &src)?.id))
+            .collect::<ParseResult<Vec<_>>>()?;
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(Plane { layout, ids })
     }
-    0
+
+    pub fn is_occupied(&self, id: u64) -> bool {
+        self.ids.binary_search(&id).is_ok()
+    }
+
+    pub fn highest(&self) -> Option<Seat> {
+        self.layout.seat(*self.ids.last()?)
+    }
+
+    /// Every free seat in the layout, including the ones before the first occupied seat and after the last
+    pub fn gaps(&self) -> impl Iterator<Item = Seat> + '_ {
+        // Each free run starts right after an occupied seat (or at 0), and ends right before the next one
+        // (or at the last seat). `None` means past `u64::MAX`.
+        let starts = iter::once(Some(0)).chain(self.ids.iter().map(|id| id.checked_add(1)));
+        let ends = self
+            .ids
+            .iter()
+            .copied()
+            .map(Some)
+            .chain(iter::once(self.layout.seats()));
+        starts
+            .zip(ends)
+            .filter_map(|run| match run {
+                (Some(start), Some(end)) if start < end => Some(start..=end - 1),
+                (Some(start), None) => Some(start..=u64::MAX),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(move |id| self.layout.seat(id))
+    }
+}
+
+#[aoc(day5, part1)]
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let plane = Plane::parse(Layout::PUZZLE, input)?;
+    Ok(plane.highest().map(|seat| seat.id).into())
+}
+
+#[aoc(day5, part2)]
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let plane = Plane::parse(Layout::PUZZLE, input)?;
+    // Ours is the only free seat with both neighbours taken
+    let seat = plane.gaps().find(|seat| {
+        let taken = |id: Option<u64>| id.is_some_and(|id| plane.is_occupied(id));
+        taken(seat.id.checked_sub(1)) && taken(seat.id.checked_add(1))
+    });
+    Ok(seat.map(|seat| seat.id).into())
 }
 
 #[cfg(test)]
@@ -90,11 +196,85 @@ mod tests {
 
     #[test]
     fn parse_seat() {
-        let mut rows = [0; 128];
-        let mut cols = [0; 8];
+        let seat = Layout::PUZZLE.decode("BFFFBBFRRR").unwrap();
+        assert_eq!(
+            seat,
+            Seat {
+                row: 70,
+                column: 7,
+                id: 567
+            }
+        );
+        assert_eq!(Layout::PUZZLE.encode(567).as_deref(), Some("BFFFBBFRRR"));
+        assert_eq!(Layout::PUZZLE.encode(1024), None);
+    }
+
+    #[test]
+    fn invalid_seat() {
+        let err = part1("BFFFBBFRRR\nBFFXBBFRRR").unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (2, 4, "X"));
+        assert!(part1("BFFFBBFRR").is_err());
+        assert!(part1("BFFFBBFFRRR").is_err());
+    }
+
+    #[test]
+    fn other_layouts() {
+        let layout = Layout::new(3, 1);
+        assert_eq!(layout.seats(), Some(16));
         assert_eq!(
-            Seat::parse("BFFFBBFRRR", &mut rows, &mut cols).get_id(),
-            567
+            layout.decode("RBFB").unwrap(),
+            Seat {
+                row: 5,
+                column: 1,
+                id: 11
+            }
         );
+        for id in 0..16 {
+            let pass = layout.encode(id).unwrap();
+            assert_eq!(layout.decode(&pass).unwrap().id, id);
+        }
+        let wide = Layout::new(40, 24);
+        assert_eq!(wide.seats(), None);
+        let pass = wide.encode(u64::MAX).unwrap();
+        assert_eq!(pass, "B".repeat(40) + &"R".repeat(24));
+        assert_eq!(wide.decode(&pass).unwrap().id, u64::MAX);
+        let columns = Layout::new(0, 2);
+        assert_eq!(columns.decode("RL").unwrap().row, 0);
+        assert!(columns.decode("FRL").is_err());
+    }
+
+    #[test]
+    fn gaps() {
+        let layout = Layout::PUZZLE;
+        let passes: Vec<String> = [3, 4, 6, 9, 10, 11]
+            .iter()
+            .map(|&id| layout.encode(id).unwrap())
+            .collect();
+        let input = passes.join("\n");
+        let plane = Plane::parse(layout, &input).unwrap();
+        let gaps: Vec<u64> = plane.gaps().map(|seat| seat.id).collect();
+        assert_eq!(gaps[..6], [0, 1, 2, 5, 7, 8]);
+        assert_eq!(gaps[6..], (12..1024).collect::<Vec<_>>()[..]);
+        assert_eq!(plane.highest().map(|seat| seat.id), Some(11));
+        assert_eq!(part2(&input), Ok(Answer::Number(5)));
+
+        // Both ends of a small plane
+        let layout = Layout::new(2, 1);
+        let gaps = |ids: &[u64]| {
+            let passes: Vec<String> = ids.iter().map(|&id| layout.encode(id).unwrap()).collect();
+            let plane = Plane::parse(layout, &passes.join("\n")).unwrap();
+            plane.gaps().map(|seat| seat.id).collect::<Vec<_>>()
+        };
+        assert_eq!(gaps(&[0, 2, 7]), vec![1, 3, 4, 5, 6]);
+        assert_eq!(gaps(&[1, 5]), vec![0, 2, 3, 4, 6, 7]);
+        assert_eq!(gaps(&[]), (0..8).collect::<Vec<_>>());
+        assert_eq!(gaps(&(0..8).collect::<Vec<_>>()), vec![]);
+        // The last seat of the widest layout
+        let layout = Layout::new(60, 4);
+        let input = [u64::MAX - 1, u64::MAX]
+            .map(|id| layout.encode(id).unwrap())
+            .join("\n");
+        let plane = Plane::parse(layout, &input).unwrap();
+        assert_eq!(plane.gaps().nth(3).map(|seat| seat.id), Some(3));
     }
 }
@@ -1,54 +1,257 @@
-struct Group {
-    answers: Vec<char>,
-    members: usize,
+//! Customs declarations, as bitsets of the questions each person answered "yes" to,
+//! and queries combining the answers of a group.
+
+use std::{
+    fmt,
+    ops::{BitAnd, BitOr, BitXor, Not},
+};
+
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+    tokens::Tokens,
+};
+
+/// A set of questions from `a` to `z`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Questions(u32);
+
+impl Questions {
+    pub const ALL: Questions = Questions((1 << 26) - 1);
+
+    /// `question` must be from `a` to `z`
+    pub fn single(question: char) -> Questions {
+        Questions(1 << (question as u8 - b'a'))
+    }
+
+    pub fn contains(self, question: char) -> bool {
+        question.is_ascii_lowercase() && self.0 & Questions::single(question).0 != 0
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = char> {
+        (b'a'..=b'z')
+            .map(char::from)
+            .filter(move |&q| self.contains(q))
+    }
+}
+
+impl BitOr for Questions {
+    type Output = Questions;
+
+    fn bitor(self, rhs: Questions) -> Questions {
+        Questions(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Questions {
+    type Output = Questions;
+
+    fn bitand(self, rhs: Questions) -> Questions {
+        Questions(self.0 & rhs.0)
+    }
+}
+
+impl BitXor for Questions {
+    type Output = Questions;
+
+    fn bitxor(self, rhs: Questions) -> Questions {
+        Questions(self.0 ^ rhs.0)
+    }
+}
+
+impl Not for Questions {
+    type Output = Questions;
+
+    fn not(self) -> Questions {
+        Questions(!self.0 & Questions::ALL.0)
+    }
+}
+
+impl fmt::Display for Questions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.iter().try_for_each(|q| write!(f, "{}", q))
+    }
+}
+
+/// The answers of each member of a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub members: Vec<Questions>,
 }
 
 impl Group {
-    fn parse(input: &str) -> Group {
-        let people: Vec<&str> = input.lines().collect();
-        let answers = {
-            let mut answers: Vec<char> = people.iter().map(|l| l.chars()).flatten().collect();
-            answers.sort();
-            answers
+    fn parse(input: &str, src: &Source) -> ParseResult<Group> {
+        // Extra blank lines between groups aren't members
+        let members = input
+            .lines()
+            .filter(|person| !person.is_empty())
+            .map(|person| {
+                person
+                    .char_indices()
+                    .try_fold(Questions::default(), |set, (i, c)| {
+                        if c.is_ascii_lowercase() {
+                            Ok(set | Questions::single(c))
+                        } else {
+                            Err(src.error(
+                                &person[i..i + c.len_utf8()],
+                                "expected a question from `a` to `z`",
+                            ))
+                        }
+                    })
+            })
+            .collect::<ParseResult<_>>()?;
+        Ok(Group { members })
+    }
+
+    /// How many members answered each question, from `a` to `z`
+    pub fn histogram(&self) -> [usize; 26] {
+        let mut counts = [0; 26];
+        for member in &self.members {
+            for q in member.iter() {
+                counts[(q as u8 - b'a') as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    /// The questions answered by at least `k` members
+    pub fn at_least(&self, k: usize) -> Questions {
+        self.histogram()
+            .iter()
+            .zip(b'a'..=b'z')
+            .filter(|(&count, _)| count >= k)
+            .fold(Questions::default(), |set, (_, q)| {
+                set | Questions::single(q as char)
+            })
+    }
+}
+
+/// Groups are separated by blank lines, with one member per line
+pub fn parse_groups(input: &str) -> ParseResult<Vec<Group>> {
+    let src = Source::new(6, input);
+    input.split("\n\n").map(|g| Group::parse(g, &src)).collect()
+}
+
+/// Picks some of the questions of a group.
+///
+/// Queries are written like `and(union, not(at_least(2)))`:
+/// - `union`: answered by anyone
+/// - `intersection`: answered by everyone
+/// - `symmetric_difference`: answered by an odd number of members
+/// - `at_least(k)`: answered by at least `k` members
+/// - `and(..)`, `or(..)`, `xor(..)` and `not(query)` combine other queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Union,
+    Intersection,
+    SymmetricDifference,
+    AtLeast(usize),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Xor(Vec<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    pub fn evaluate(&self, group: &Group) -> Questions {
+        let fold = |queries: &[Query], f: fn(Questions, Questions) -> Questions| {
+            queries
+                .iter()
+                .map(|q| q.evaluate(group))
+                .reduce(f)
+                .unwrap_or_default()
         };
-        Group {
-            answers,
-            members: people.len(),
+        match self {
+            Query::Union => group
+                .members
+                .iter()
+                .fold(Questions::default(), |a, &b| a | b),
+            Query::Intersection => group
+                .members
+                .iter()
+                .copied()
+                .reduce(BitAnd::bitand)
+                .unwrap_or_default(),
+            Query::SymmetricDifference => group
+                .members
+                .iter()
+                .fold(Questions::default(), |a, &b| a ^ b),
+            Query::AtLeast(k) => group.at_least(*k),
+            Query::And(queries) => fold(queries, BitAnd::bitand),
+            Query::Or(queries) => fold(queries, BitOr::bitor),
+            Query::Xor(queries) => fold(queries, BitXor::bitxor),
+            Query::Not(query) => !query.evaluate(group),
         }
     }
 
-    fn answers(&mut self) -> usize {
-        self.answers.dedup();
-        self.answers.len()
+    /// The number of questions picked, summed over every group
+    pub fn count(&self, groups: &[Group]) -> usize {
+        groups.iter().map(|g| self.evaluate(g).len()).sum()
     }
 
-    fn consensus(&self) -> usize {
-        // Operate on the deduplicated version (we want to filter the unique answers)
-        let mut dedup = self.answers.clone();
-        dedup.dedup();
-        dedup
-            .iter()
-            // Count the occurrences in the original version, and check if they're the same as the member count
-            .filter(|a| self.answers.iter().filter(|c| c == a).count() == self.members)
-            .count()
+    /// How many groups each question was picked in, from `a` to `z`
+    pub fn histogram(&self, groups: &[Group]) -> [usize; 26] {
+        let mut counts = [0; 26];
+        for group in groups {
+            for q in self.evaluate(group).iter() {
+                counts[(q as u8 - b'a') as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    pub fn parse(input: &str) -> ParseResult<Query> {
+        let src = Source::new(0, input);
+        let mut tokens = Tokens::new(&src, input)?;
+        let query = query(&mut tokens)?;
+        tokens.finish("the end of the query")?;
+        Ok(query)
     }
 }
 
-fn parse_input(input: &str) -> Vec<Group> {
-    input.split("\n\n").map(|g| Group::parse(g)).collect()
+fn query(tokens: &mut Tokens) -> ParseResult<Query> {
+    let name = tokens.token("a query")?;
+    Ok(match name {
+        "union" => Query::Union,
+        "intersection" => Query::Intersection,
+        "symmetric_difference" => Query::SymmetricDifference,
+        "at_least" => {
+            tokens.expect("(")?;
+            let k = tokens.token("a number of members")?;
+            let k = tokens.src().parse(k)?;
+            tokens.expect(")")?;
+            Query::AtLeast(k)
+        }
+        "and" | "or" | "xor" | "not" => {
+            let mut queries = tokens.list(query)?;
+            match name {
+                "and" => Query::And(queries),
+                "or" => Query::Or(queries),
+                "xor" => Query::Xor(queries),
+                _ if queries.len() == 1 => Query::Not(Box::new(queries.pop().unwrap())),
+                _ => return Err(tokens.error(name, "`not` takes a single query")),
+            }
+        }
+        _ => return Err(tokens.error(name, "unknown query")),
+    })
 }
 
 #[aoc(day6, part1)]
-fn part1(input: &str) -> usize {
-    parse_input(input)
-        .into_iter()
-        .map(|mut g| g.answers())
-        .sum()
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    Ok(Query::Union.count(&parse_groups(input)?).into())
 }
 
 #[aoc(day6, part2)]
-fn part2(input: &str) -> usize {
-    parse_input(input).iter().map(|g| g.consensus()).sum()
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    Ok(Query::Intersection.count(&parse_groups(input)?).into())
 }
 
 #[cfg(test)]
@@ -72,7 +275,7 @@ a
 a
 
 b";
-        assert_eq!(part1(input), 11);
+        assert_eq!(part1(input), Ok(Answer::Number(11)));
     }
 
     #[test]
@@ -92,6 +295,49 @@ a
 a
 
 b";
-        assert_eq!(part2(input), 6);
+        assert_eq!(part2(input), Ok(Answer::Number(6)));
+    }
+
+    #[test]
+    fn empty_groups() {
+        assert_eq!(part1(""), Ok(Answer::Number(0)));
+        assert_eq!(part2(""), Ok(Answer::Number(0)));
+        assert_eq!(part2("ab\n\n\nab\n\n\n\nabc\nb\n"), Ok(Answer::Number(5)));
+        let groups = parse_groups("ab\n\n\n\nb").unwrap();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(
+            Query::Intersection.evaluate(&groups[1]),
+            Questions::default()
+        );
+    }
+
+    #[test]
+    fn queries() {
+        let groups = parse_groups("abc\n\na\nb\nc\n\nab\nac\n\na\na\na\na\n\nb").unwrap();
+        assert_eq!(groups[2].histogram()[..3], [2, 1, 1]);
+        let count = |query: &str| Query::parse(query).unwrap().count(&groups);
+        assert_eq!(count("union"), 11);
+        assert_eq!(count("intersection"), 6);
+        assert_eq!(count("symmetric_difference"), 9);
+        assert_eq!(count("at_least(2)"), 2);
+        assert_eq!(count("and(union, not(intersection))"), 11 - 6);
+        assert_eq!(count("xor(union, at_least(2), intersection)"), 7);
+        assert_eq!(count("or(at_least(4), at_least(3))"), 1);
+        let histogram = Query::parse("intersection").unwrap().histogram(&groups);
+        assert_eq!(histogram[..3], [3, 2, 1]);
+        assert_eq!(Query::AtLeast(2).evaluate(&groups[2]).to_string(), "a");
+    }
+
+    #[test]
+    fn invalid_queries() {
+        let err = Query::parse("and(union, at_least(x))").unwrap_err();
+        assert_eq!((err.column, err.text.as_str()), (21, "x"));
+        let err = Query::parse("not(union, union)").unwrap_err();
+        assert_eq!(err.reason, "`not` takes a single query");
+        assert!(Query::parse("union union").is_err());
+        assert!(Query::parse("and(union").is_err());
+        assert!(Query::parse("everyone").is_err());
+        let err = part1("ab\nA").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
     }
 }
@@ -1,86 +1,469 @@
-use fxhash::FxHashMap;
+//! Rules saying which bags go inside which, as a graph from each bag to its contents.
+
+use std::{cell::OnceCell, error::Error, fmt, fmt::Write};
+
+use fxhash::{FxHashMap, FxHashSet};
 use regex::Regex;
 
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    json_string,
+    solver::Answer,
+};
+
 lazy_static::lazy_static! {
     static ref REGEX: Regex = Regex::new(r#"(.+) bags contain (?:(no other bags)|(.+))\."#).unwrap();
-    static ref SINGLE: Regex = Regex::new(r#"\s?(\d) (.+) bags?"#).unwrap();
+    static ref SINGLE: Regex = Regex::new(r#"\s?(\d+) (.+) bags?"#).unwrap();
 }
 
-#[derive(Default)]
-struct Bag {
-    children: Vec<BagChild>,
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bag {
+    pub children: Vec<BagChild>,
 }
 
-struct BagChild {
-    qty: usize,
-    name: String,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagChild {
+    pub qty: usize,
+    pub name: String,
 }
 
 impl Bag {
-    fn parse(line: &str) -> (String, Bag) {
-        let caps = REGEX.captures(line).expect("line didn't match pattern");
+    fn parse(line: &str, src: &Source) -> ParseResult<(String, Bag)> {
+        let caps = REGEX
+            .captures(line)
+            .ok_or_else(|| src.error(line, "expected `<color> bags contain <contents>.`"))?;
         let name = caps[1].to_string();
-        if let Some(_) = caps.get(2) {
-            return (name, Bag::default());
+        if caps.get(2).is_some() {
+            return Ok((name, Bag::default()));
         }
-        let children = caps[3]
+        let children = caps
+            .get(3)
+            .unwrap()
+            .as_str()
             .split(',')
             .map(|part| {
-                let caps = SINGLE.captures(part).expect("part didn't match pattern");
-                BagChild {
-                    qty: caps[1].parse().unwrap(),
+                let caps = SINGLE
+                    .captures(part)
+                    .ok_or_else(|| src.error(part, "expected `<quantity> <color> bag(s)`"))?;
+                Ok(BagChild {
+                    qty: src.parse(caps.get(1).unwrap().as_str())?,
                     name: caps[2].to_string(),
+                })
+            })
+            .collect::<ParseResult<_>>()?;
+        Ok((name, Bag { children }))
+    }
+}
+
+/// Reads one rule per line, by color
+pub fn parse_rules(input: &str) -> ParseResult<FxHashMap<String, Bag>> {
+    let src = Source::new(7, input);
+    input.lines().map(|l| Bag::parse(l, &src)).collect()
+}
+
+/// The index of a color in a [`BagGraph`]
+pub type BagId = usize;
+
+/// Bags that (eventually) contain themselves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// Each bag contains the next one, and the last contains the first
+    pub cycle: Vec<String>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bags contain each other: ")?;
+        for name in &self.cycle {
+            write!(f, "{} -> ", name)?;
+        }
+        write!(f, "{}", self.cycle[0])
+    }
+}
+
+impl Error for CycleError {}
+
+/// The containment rules, with an edge from each bag to its contents and back.
+/// Colors that are only mentioned as contents are bags with nothing inside.
+///
+/// The queries are computed for every bag at once the first time they are needed.
+#[derive(Debug, Clone)]
+pub struct BagGraph {
+    names: Vec<String>,
+    ids: FxHashMap<String, BagId>,
+    /// `(bag, quantity)` for each bag inside
+    contents: Vec<Vec<(BagId, usize)>>,
+    /// `(bag, quantity)` for each bag this one goes in
+    containers: Vec<Vec<(BagId, usize)>>,
+    /// Every bag after its contents
+    order: Vec<BagId>,
+    ancestors: OnceCell<Vec<FxHashSet<BagId>>>,
+    descendants: OnceCell<Vec<FxHashSet<BagId>>>,
+    totals: OnceCell<Vec<Option<u64>>>,
+}
+
+impl BagGraph {
+    pub fn new(bags: &FxHashMap<String, Bag>) -> Result<BagGraph, CycleError> {
+        let mut graph = BagGraph {
+            names: vec![],
+            ids: FxHashMap::default(),
+            contents: vec![],
+            containers: vec![],
+            order: vec![],
+            ancestors: OnceCell::new(),
+            descendants: OnceCell::new(),
+            totals: OnceCell::new(),
+        };
+        // Sorted, so that ids don't depend on the order of the map
+        let mut names: Vec<&String> = bags.keys().collect();
+        names.sort();
+        for name in names {
+            let bag = graph.intern(name);
+            for child in &bags[name].children {
+                let id = graph.intern(&child.name);
+                graph.contents[bag].push((id, child.qty));
+                graph.containers[id].push((bag, child.qty));
+            }
+        }
+        graph.order = graph.sort()?;
+        Ok(graph)
+    }
+
+    fn intern(&mut self, name: &str) -> BagId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.contents.push(vec![]);
+        self.containers.push(vec![]);
+        id
+    }
+
+    /// Orders the bags so that each one comes after its contents, starting from the empty ones
+    fn sort(&self) -> Result<Vec<BagId>, CycleError> {
+        let mut left: Vec<usize> = self.contents.iter().map(Vec::len).collect();
+        let mut order: Vec<BagId> = (0..self.len()).filter(|&id| left[id] == 0).collect();
+        let mut i = 0;
+        while let Some(&id) = order.get(i) {
+            for &(container, _) in &self.containers[id] {
+                left[container] -= 1;
+                if left[container] == 0 {
+                    order.push(container);
                 }
+            }
+            i += 1;
+        }
+        if order.len() == self.len() {
+            return Ok(order);
+        }
+        // Every bag left contains another one that's left, so following them must loop
+        let mut path = vec![(0..self.len()).find(|&id| left[id] > 0).unwrap()];
+        loop {
+            let last = *path.last().unwrap();
+            let &(next, _) = self.contents[last]
+                .iter()
+                .find(|&&(c, _)| left[c] > 0)
+                .unwrap();
+            if let Some(start) = path.iter().position(|&id| id == next) {
+                let cycle = path[start..]
+                    .iter()
+                    .map(|&id| self.names[id].clone())
+                    .collect();
+                return Err(CycleError { cycle });
+            }
+            path.push(next);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Option<BagId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: BagId) -> &str {
+        &self.names[id]
+    }
+
+    /// The bags directly inside, with their quantities
+    pub fn contents(&self, id: BagId) -> &[(BagId, usize)] {
+        &self.contents[id]
+    }
+
+    /// The bags this one goes directly in, with how many of it they hold
+    pub fn containers(&self, id: BagId) -> &[(BagId, usize)] {
+        &self.containers[id]
+    }
+
+    /// Unions the sets of the neighbours of each bag, visiting the bags in `order`
+    fn closure(
+        &self,
+        order: impl Iterator<Item = BagId>,
+        edges: &[Vec<(BagId, usize)>],
+    ) -> Vec<FxHashSet<BagId>> {
+        let mut sets = vec![FxHashSet::default(); self.len()];
+        for id in order {
+            let mut set = FxHashSet::default();
+            for &(next, _) in &edges[id] {
+                set.insert(next);
+                set.extend(&sets[next]);
+            }
+            sets[id] = set;
+        }
+        sets
+    }
+
+    /// The bags that contain this one, directly or not
+    pub fn ancestors(&self, id: BagId) -> &FxHashSet<BagId> {
+        &self
+            .ancestors
+            .get_or_init(|| self.closure(self.order.iter().rev().copied(), &self.containers))[id]
+    }
+
+    /// The bags inside this one, directly or not
+    pub fn descendants(&self, id: BagId) -> &FxHashSet<BagId> {
+        &self
+            .descendants
+            .get_or_init(|| self.closure(self.order.iter().copied(), &self.contents))[id]
+    }
+
+    /// The bags to export, by name: every bag, or `root` and the bags inside it
+    fn selection(&self, root: Option<BagId>) -> Vec<BagId> {
+        let mut bags: Vec<BagId> = match root {
+            Some(root) => std::iter::once(root)
+                .chain(self.descendants(root).iter().copied())
+                .collect(),
+            None => (0..self.len()).collect(),
+        };
+        bags.sort_unstable_by_key(|&id| &self.names[id]);
+        bags
+    }
+
+    /// A Graphviz graph with an edge from each bag to its contents, labelled with the quantity
+    pub fn to_dot(&self, root: Option<BagId>) -> String {
+        let mut res = String::from("digraph bags {\n");
+        for id in self.selection(root) {
+            if self.contents[id].is_empty() {
+                writeln!(res, "    {};", json_string(&self.names[id])).unwrap();
+            }
+            for &(child, qty) in &self.contents[id] {
+                writeln!(
+                    res,
+                    "    {} -> {} [label={}];",
+                    json_string(&self.names[id]),
+                    json_string(&self.names[child]),
+                    qty
+                )
+                .unwrap();
+            }
+        }
+        res + "}\n"
+    }
+
+    /// A JSON object with the contents of each bag, by color. [`parse_json`] reads it back.
+    pub fn to_json(&self, root: Option<BagId>) -> String {
+        let entries: Vec<String> = self
+            .selection(root)
+            .into_iter()
+            .map(|id| {
+                let contents: Vec<String> = self.contents[id]
+                    .iter()
+                    .map(|&(child, qty)| format!("{}: {}", json_string(&self.names[child]), qty))
+                    .collect();
+                format!(
+                    "  {}: {{{}}}",
+                    json_string(&self.names[id]),
+                    contents.join(", ")
+                )
             })
             .collect();
-        (name, Bag { children })
+        format!("{{\n{}\n}}\n", entries.join(",\n"))
+    }
+
+    /// How many bags are inside this one, counting every level.
+    /// `None` if there are more than a `u64` can count.
+    pub fn total_contents(&self, id: BagId) -> Option<u64> {
+        self.totals.get_or_init(|| {
+            let mut totals = vec![Some(0u64); self.len()];
+            for &id in &self.order {
+                totals[id] = self.contents[id]
+                    .iter()
+                    .try_fold(0u64, |sum, &(child, qty)| {
+                        let bags = totals[child]?.checked_add(1)?;
+                        sum.checked_add(bags.checked_mul(qty as u64)?)
+                    });
+            }
+            totals
+        })[id]
+    }
+}
+
+/// Reads rules written by [`BagGraph::to_json`]
+pub fn parse_json(input: &str) -> ParseResult<FxHashMap<String, Bag>> {
+    let mut reader = JsonReader {
+        src: Source::new(7, input),
+        rest: input,
+    };
+    let mut bags = FxHashMap::default();
+    reader.object(|reader, name| {
+        let mut children = vec![];
+        reader.object(|reader, child| {
+            let qty = reader.number()?;
+            children.push(BagChild { qty, name: child });
+            Ok(())
+        })?;
+        bags.insert(name, Bag { children });
+        Ok(())
+    })?;
+    reader.skip_whitespace();
+    if !reader.rest.is_empty() {
+        return Err(reader
+            .src
+            .error(reader.rest, "expected the end of the file"));
     }
+    Ok(bags)
 }
 
-fn parse_input(input: &str) -> FxHashMap<String, Bag> {
-    input.lines().map(|l| Bag::parse(l)).collect()
+/// Just enough JSON for objects of strings and numbers
+struct JsonReader<'a> {
+    src: Source<'a>,
+    rest: &'a str,
 }
 
-fn has_gold(map: &FxHashMap<String, Bag>, bag: &Bag) -> bool {
-    for child in &bag.children {
-        if child.name == "shiny gold" {
-            return true;
+impl<'a> JsonReader<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// The next character, or the empty end of the input
+    fn next(&self) -> &'a str {
+        &self.rest[..self.rest.chars().next().map_or(0, char::len_utf8)]
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
         }
-        let bag = map.get(&child.name);
-        if let Some(bag) = bag {
-            if has_gold(&map, &bag) {
-                return true;
+    }
+
+    fn expect(&mut self, c: char) -> ParseResult<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.src.error(self.next(), format!("expected `{}`", c)))
+        }
+    }
+
+    /// Calls `value` with each key, to read the value after the `:`
+    fn object(
+        &mut self,
+        mut value: impl FnMut(&mut Self, String) -> ParseResult<()>,
+    ) -> ParseResult<()> {
+        self.expect('{')?;
+        if self.eat('}') {
+            return Ok(());
+        }
+        loop {
+            let key = self.string()?;
+            self.expect(':')?;
+            value(self, key)?;
+            if self.eat('}') {
+                return Ok(());
             }
+            self.expect(',')?;
         }
     }
-    false
-}
 
-fn deep_count(map: &FxHashMap<String, Bag>, bag: &Bag, count: &mut usize) {
-    for child in &bag.children {
-        *count += child.qty;
-        let bag = map.get(&child.name);
-        if let Some(bag) = bag {
-            let mut tmp = 0;
-            deep_count(&map, &bag, &mut tmp);
-            tmp *= child.qty;
-            *count += tmp;
+    fn string(&mut self) -> ParseResult<String> {
+        self.expect('"')?;
+        let mut res = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(res);
+                }
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some((_, 'u')) => {
+                            let start = i + 2;
+                            let code = self.rest.get(start..start + 4).unwrap_or("");
+                            let c = u32::from_str_radix(code, 16).ok().and_then(char::from_u32);
+                            let c = c.ok_or_else(|| {
+                                self.src.error(&self.rest[i..], "invalid unicode escape")
+                            })?;
+                            chars.nth(3);
+                            c
+                        }
+                        Some((_, c @ ('"' | '\\' | '/'))) => c,
+                        Some((_, 'n')) => '\n',
+                        Some((_, 't')) => '\t',
+                        _ => return Err(self.src.error(&self.rest[i..], "invalid escape")),
+                    };
+                    res.push(escaped);
+                }
+                c => res.push(c),
+            }
+        }
+        Err(self.src.error(self.rest, "unterminated string"))
+    }
+
+    fn number(&mut self) -> ParseResult<usize> {
+        self.skip_whitespace();
+        let len = self
+            .rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest.len());
+        let (number, rest) = self.rest.split_at(len);
+        if number.is_empty() {
+            return Err(self.src.error(self.next(), "expected a quantity"));
         }
+        self.rest = rest;
+        self.src.parse(number)
     }
 }
 
+fn build_graph(input: &str) -> ParseResult<BagGraph> {
+    BagGraph::new(&parse_rules(input)?).map_err(|e| {
+        // Point to the rule of a bag in the cycle
+        let rule = input
+            .lines()
+            .find(|line| line.starts_with(&format!("{} bags", e.cycle[0])))
+            .unwrap_or(input);
+        Source::new(7, input).error(rule, e.to_string())
+    })
+}
+
 #[aoc(day7, part1)]
-fn part1(input: &str) -> usize {
-    let bags = parse_input(input);
-    bags.values().filter(|b| has_gold(&bags, b)).count()
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let graph = build_graph(input)?;
+    let count = graph
+        .id("shiny gold")
+        .map_or(0, |gold| graph.ancestors(gold).len());
+    Ok(count.into())
 }
 
 #[aoc(day7, part2)]
-fn part2(input: &str) -> usize {
-    let bags = parse_input(input);
-    let mut count = 0;
-    deep_count(&bags, &bags["shiny gold"], &mut count);
-    count
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let graph = build_graph(input)?;
+    let count = graph
+        .id("shiny gold")
+        .map_or(Some(0), |gold| graph.total_contents(gold));
+    Ok(count.into())
 }
 
 #[cfg(test)]
@@ -98,7 +481,7 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
-        assert_eq!(part1(input), 4);
+        assert_eq!(part1(input), Ok(Answer::Number(4)));
     }
 
     #[test]
@@ -119,7 +502,123 @@ dark olive bags contain 3 faded blue bags, 4 dotted black bags.
 vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
-        assert_eq!(part2(input), 126);
-        assert_eq!(part2(part1), 32);
+        assert_eq!(part2(input), Ok(Answer::Number(126)));
+        assert_eq!(part2(part1), Ok(Answer::Number(32)));
+    }
+
+    #[test]
+    fn graph_queries() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 12 faded blue bags.
+shiny gold bags contain 1 faded blue bag.";
+        let graph = BagGraph::new(&parse_rules(input).unwrap()).unwrap();
+        assert_eq!(graph.len(), 5);
+        let id = |name| graph.id(name).unwrap();
+        let names = |set: &FxHashSet<BagId>| {
+            let mut names: Vec<&str> = set.iter().map(|&id| graph.name(id)).collect();
+            names.sort_unstable();
+            names
+        };
+        assert_eq!(
+            names(graph.ancestors(id("faded blue"))),
+            ["bright white", "light red", "muted yellow", "shiny gold"]
+        );
+        assert_eq!(
+            names(graph.descendants(id("muted yellow"))),
+            ["faded blue", "shiny gold"]
+        );
+        assert!(graph.descendants(id("faded blue")).is_empty());
+        assert_eq!(graph.total_contents(id("muted yellow")), Some(2 * 2 + 12));
+        assert_eq!(
+            graph.total_contents(id("light red")),
+            Some((1 + 2) + 2 * (1 + 16))
+        );
+        assert_eq!(graph.containers(id("shiny gold")).len(), 2);
+    }
+
+    #[test]
+    fn cycles() {
+        let input = "light red bags contain 1 dark blue bag.
+dark blue bags contain 2 shiny gold bags.
+shiny gold bags contain 3 light red bags, 4 faded blue bags.";
+        let err = BagGraph::new(&parse_rules(input).unwrap()).unwrap_err();
+        assert_eq!(err.cycle.len(), 3);
+        let err = part1(input).unwrap_err();
+        assert!(err.reason.starts_with("bags contain each other: "));
+        assert_eq!(err.column, 1);
+        let err = part2("a bags contain 1 a bag.").unwrap_err();
+        assert_eq!(err.reason, "bags contain each other: a -> a");
+    }
+
+    #[test]
+    fn too_many_bags() {
+        // Six levels of 1000 bags each fit in a u64, but 1000^7 bags don't
+        let rules = |depth: usize| {
+            let mut rules: Vec<String> = (0..depth)
+                .map(|i| format!("level {} bags contain 1000 level {} bags.", i, i + 1))
+                .collect();
+            rules.push(format!("level {} bags contain no other bags.", depth));
+            rules.join("\n").replace("level 0", "shiny gold")
+        };
+        let total: u64 = (1..=6).map(|i| 1000u64.pow(i)).sum();
+        assert_eq!(part2(&rules(6)), Ok(Answer::Number(total.into())));
+        assert_eq!(part2(&rules(8)), Ok(Answer::NoSolution));
+        let graph = build_graph(&rules(8)).unwrap();
+        assert_eq!(
+            graph.total_contents(graph.id("level 2").unwrap()),
+            Some(total)
+        );
+    }
+
+    #[test]
+    fn export() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 faded blue bag.
+faded blue bags contain no other bags.";
+        let rules = parse_rules(input).unwrap();
+        let graph = BagGraph::new(&rules).unwrap();
+        let json = graph.to_json(None);
+        assert_eq!(parse_json(&json), Ok(rules));
+        assert_eq!(
+            BagGraph::new(&parse_json(&json).unwrap())
+                .unwrap()
+                .to_json(None),
+            json
+        );
+
+        let yellow = graph.id("muted yellow");
+        assert_eq!(
+            graph.to_dot(yellow),
+            r#"digraph bags {
+    "faded blue";
+    "muted yellow" -> "shiny gold" [label=2];
+    "muted yellow" -> "faded blue" [label=9];
+    "shiny gold" -> "faded blue" [label=1];
+}
+"#
+        );
+        assert_eq!(
+            graph.to_json(yellow),
+            r#"{
+  "faded blue": {},
+  "muted yellow": {"shiny gold": 2, "faded blue": 9},
+  "shiny gold": {"faded blue": 1}
+}
+"#
+        );
+    }
+
+    #[test]
+    fn invalid_json() {
+        let rules = parse_json(r#"{"a \"b\"": {"c\u00e9": 3}}"#).unwrap();
+        assert_eq!(rules["a \"b\""].children[0].name, "c\u{e9}");
+        let err = parse_json("{\n  \"a\": {\"b\": x}\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 14));
+        assert!(parse_json(r#"{"a": {}"#).is_err());
+        assert!(parse_json(r#"{"a": {}} x"#).is_err());
+        assert!(parse_json(r#"{"a: {}}"#).is_err());
     }
 }
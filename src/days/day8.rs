@@ -1,55 +1,39 @@
-use fxhash::FxHashSet;
-use std::sync::{mpsc, Mutex};
+use crate::{
+    assembly::{Opcode, Outcome, Program},
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
 
-use crate::assembly::{Instruction, Opcode, Program};
-
-fn run_program(program: &Program) -> (i32, isize) {
-    let (tx, rx) = mpsc::channel();
-    let known = Mutex::new(FxHashSet::default());
-    let status = program.run(|_inst| {
-        if !known.lock().unwrap().insert(program.instruction_ptr()) {
-            program.stop();
-            tx.send(program.accumulator()).unwrap();
+/// Day 8 programs only use the handheld's opcodes, so their control flow is always static
+fn parse_input(input: &str) -> ParseResult<Program> {
+    let program = Program::from_input(input, 8)?;
+    let src = Source::new(8, input);
+    for (i, inst) in program.instructions().iter().enumerate() {
+        let handheld = matches!(
+            inst.opcode,
+            Opcode::Accumulate | Opcode::Jump | Opcode::NoOp
+        );
+        if !handheld || inst.args[0].register().is_some() {
+            let code = program.span(i).map_or(input, |span| &input[span]);
+            return Err(src.error(code, "expected `acc`, `jmp` or `nop` with a number"));
         }
-        return;
-    });
-    (
-        status,
-        rx.try_recv().unwrap_or_else(|_e| program.accumulator()),
-    )
-}
-
-fn change_op(insts: &[Instruction], from: Opcode, to: Opcode) -> Option<isize> {
-    insts
-        .iter()
-        .enumerate()
-        .filter_map(|(i, inst)| if inst.opcode == from { Some(i) } else { None })
-        .filter_map(|idx| {
-            let mut new_insts = insts.to_vec();
-            let arg = new_insts[idx].arg;
-            new_insts[idx] = Instruction { opcode: to, arg };
-            let (status, res) = run_program(&Program::with(new_insts));
-            if status == 0 {
-                Some(res)
-            } else {
-                None
-            }
-        })
-        .next()
+    }
+    Ok(program)
 }
 
 #[aoc(day8, part1)]
-fn part1(input: &str) -> isize {
-    let program = Program::from_input(input);
-    run_program(&program).1
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let program = parse_input(input)?;
+    match program.analyze().expect("static control flow") {
+        Outcome::Looped { acc, .. } | Outcome::Terminated { acc } => Ok(acc.into()),
+    }
 }
 
 #[aoc(day8, part2)]
-fn part2(input: &str) -> isize {
-    let program = Program::from_input(input);
-    let insts = program.instructions();
-    change_op(insts, Opcode::NoOp, Opcode::Jump)
-        .unwrap_or_else(|| change_op(insts, Opcode::Jump, Opcode::NoOp).unwrap())
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let program = parse_input(input)?;
+    let patch = program.find_patch().expect("static control flow");
+    Ok(patch.map(|p| p.acc).into())
 }
 
 #[cfg(test)]
@@ -67,7 +51,7 @@ acc -99
 acc +1
 jmp -4
 acc +6";
-        assert_eq!(part1(input), 5);
+        assert_eq!(part1(input), Ok(Answer::Number(5)));
     }
 
     #[test]
@@ -81,6 +65,14 @@ acc -99
 acc +1
 jmp -4
 acc +6";
-        assert_eq!(part2(input), 8);
+        assert_eq!(part2(input), Ok(Answer::Number(8)));
+    }
+
+    #[test]
+    fn error_lines() {
+        let err = part1("# fix me\n\nstart: nop +0\n  jmp start ; back\nset a 3\n").unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (5, 1, "set a 3"));
+        let err = part2("acc +1\nend:\n    acc b  # register").unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (3, 5, "acc b"));
     }
 }
@@ -1,55 +1,118 @@
+//! The XMAS cipher: after a preamble, each number must be the sum of two different numbers among the ones just before it.
+
+use std::collections::{hash_map::Entry, VecDeque};
+
+use fxhash::FxHashMap;
 use itertools::Itertools;
 
-fn parse_input(input: &str) -> Vec<i64> {
-    input.lines().map(|l| l.parse().unwrap()).collect()
+use crate::{
+    error::{ParseError, ParseResult, Source},
+    solver::Answer,
+};
+
+const PREAMBLE: usize = 25;
+
+fn parse_input(input: &str) -> ParseResult<Vec<i64>> {
+    let src = Source::new(9, input);
+    input.lines().map(|l| src.parse(l)).collect()
+}
+
+/// Checks numbers one at a time against the last `preamble` ones
+#[derive(Debug, Clone)]
+pub struct XmasDecoder {
+    preamble: usize,
+    window: VecDeque<i64>,
+    /// How many pairs of different numbers in the window add up to each sum.
+    /// Sums without any pair are removed, so this never holds more than `preamble²/2` sums.
+    sums: FxHashMap<i64, usize>,
+}
+
+impl XmasDecoder {
+    pub fn new(preamble: usize) -> XmasDecoder {
+        XmasDecoder {
+            preamble,
+            window: VecDeque::with_capacity(preamble + 1),
+            sums: FxHashMap::default(),
+        }
+    }
+
+    /// Whether `n` can come next. Anything can while the preamble isn't complete.
+    pub fn is_valid(&self, n: i64) -> bool {
+        self.window.len() < self.preamble || self.sums.contains_key(&n)
+    }
+
+    /// Adds `n` to the window, dropping the oldest number if it's full. Returns whether `n` was valid.
+    pub fn push(&mut self, n: i64) -> bool {
+        let valid = self.is_valid(n);
+        if self.window.len() == self.preamble {
+            if let Some(old) = self.window.pop_front() {
+                for &m in self.window.iter().filter(|&&m| m != old) {
+                    if let Entry::Occupied(mut count) = self.sums.entry(old + m) {
+                        *count.get_mut() -= 1;
+                        if *count.get() == 0 {
+                            count.remove();
+                        }
+                    }
+                }
+            }
+        }
+        if self.preamble > 0 {
+            for &m in self.window.iter().filter(|&&m| m != n) {
+                *self.sums.entry(n + m).or_default() += 1;
+            }
+            self.window.push_back(n);
+        }
+        valid
+    }
+
+    /// The numbers of the stream that aren't valid, checked as they come
+    pub fn invalid<'s, I>(&'s mut self, nums: I) -> impl Iterator<Item = i64> + 's
+    where
+        I: IntoIterator<Item = i64>,
+        I::IntoIter: 's,
+    {
+        nums.into_iter().filter(move |&n| !self.push(n))
+    }
+
+    pub fn first_invalid(&mut self, nums: impl IntoIterator<Item = i64>) -> Option<i64> {
+        nums.into_iter().find(|&n| !self.push(n))
+    }
 }
 
-fn calc_part1(nums: &[i64], preamble_len: usize) -> i64 {
-    for i in preamble_len..nums.len() {
-        let num = nums[i];
-        let slice = &nums[(i - preamble_len)..i];
-        if !slice.iter().any(|i| {
-            slice
-                .iter()
-                .find(|i2| **i2 == num - i)
-                .and_then(|i2| if i2 == i { None } else { Some(i2) })
-                .is_some()
-        }) {
-            return num;
+/// The first run of at least two contiguous numbers that add up to `target`.
+/// The numbers can't be negative, so that the window only has to move forward.
+pub fn find_range(nums: &[i64], target: i64) -> Option<&[i64]> {
+    let (mut start, mut sum) = (0, 0);
+    for end in 0..nums.len() {
+        sum += nums[end];
+        while sum > target && start < end {
+            sum -= nums[start];
+            start += 1;
+        }
+        if sum == target && end > start {
+            return Some(&nums[start..=end]);
         }
     }
-    0
+    None
 }
 
 #[aoc(day9, part1)]
-fn part1(input: &str) -> i64 {
-    let nums = parse_input(input);
-    calc_part1(&nums, 25)
+pub(crate) fn part1(input: &str) -> Result<Answer, ParseError> {
+    let nums = parse_input(input)?;
+    Ok(XmasDecoder::new(PREAMBLE)
+        .first_invalid(nums.iter().copied())
+        .into())
 }
 
 #[aoc(day9, part2)]
-fn part2(input: &str) -> i64 {
-    let mut nums = parse_input(input);
-    let part1 = calc_part1(&nums, 25);
-    nums.retain(|&n| n < part1);
-    for i in 0..nums.len() {
-        let mut sum = nums[i];
-        let mut known = vec![];
-        known.push(nums[i]);
-        for j in (i + 1)..nums.len() {
-            sum += nums[j];
-            known.push(nums[j]);
-            if sum == part1 {
-                return known
-                    .iter()
-                    .minmax()
-                    .into_option()
-                    .map(|(a, b)| a + b)
-                    .unwrap();
-            }
-        }
-    }
-    0
+pub(crate) fn part2(input: &str) -> Result<Answer, ParseError> {
+    let nums = parse_input(input)?;
+    let weakness = XmasDecoder::new(PREAMBLE)
+        .first_invalid(nums.iter().copied())
+        .and_then(|invalid| find_range(&nums, invalid))
+        .and_then(|range| range.iter().minmax().into_option())
+        .map(|(min, max)| min + max);
+    Ok(weakness.into())
 }
 
 #[cfg(test)]
@@ -59,6 +122,39 @@ mod tests {
     #[test]
     fn part1_example() {
         let input = example!(35 20 15 25 47 40 62 55 65 95 102 117 150 182 127 219 299 277 309 576);
-        assert_eq!(calc_part1(&parse_input(input), 5), 127);
+        let nums = parse_input(input).unwrap();
+        assert_eq!(
+            XmasDecoder::new(5).first_invalid(nums.iter().copied()),
+            Some(127)
+        );
+    }
+
+    #[test]
+    fn part2_example() {
+        let input = example!(35 20 15 25 47 40 62 55 65 95 102 117 150 182 127 219 299 277 309 576);
+        let nums = parse_input(input).unwrap();
+        assert_eq!(find_range(&nums, 127), Some(&nums[2..6]));
+        assert_eq!(find_range(&nums, 36), None);
+        assert_eq!(find_range(&nums, 1), None);
+    }
+
+    #[test]
+    fn streaming() {
+        let mut decoder = XmasDecoder::new(3);
+        let invalid: Vec<i64> = decoder.invalid(vec![1, 2, 3, 4, 6, 6, 12, 5, 18]).collect();
+        // The second 6 isn't 3 + 3 and 12 isn't 6 + 6, the numbers must be different
+        assert_eq!(invalid, vec![6, 12, 5]);
+        assert!(decoder.is_valid(23));
+        assert!(!decoder.is_valid(11));
+        let mut decoder = XmasDecoder::new(0);
+        assert!(!decoder.push(1));
+        assert_eq!(decoder.first_invalid(1..), Some(1));
+        let mut decoder = XmasDecoder::new(2);
+        assert_eq!(decoder.first_invalid((1..).map(|n| n * n)), Some(9));
+
+        // Old sums are dropped as the window moves
+        let mut decoder = XmasDecoder::new(5);
+        assert_eq!(decoder.invalid(0..10_000).count(), 10_000 - 10);
+        assert_eq!(decoder.sums.len(), 7);
     }
 }
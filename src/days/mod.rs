@@ -1,25 +1,52 @@
-mod day1;
-mod day10;
-mod day11;
-mod day12;
-mod day13;
-mod day14;
-mod day15;
-mod day16;
-mod day17;
-mod day18;
-mod day19;
-mod day2;
-mod day20;
-mod day21;
-mod day22;
-mod day23;
-mod day24;
-mod day25;
-mod day3;
-mod day4;
-mod day5;
-mod day6;
-mod day7;
-mod day8;
-mod day9;
+//! One public module per puzzle day, each exporting `part1`/`part2` alongside its
+//! puzzle types. `register` wires the parts into the solver.
+
+use crate::solver::{Part, Registry};
+
+pub mod day1;
+pub mod day10;
+pub mod day11;
+pub mod day12;
+pub mod day13;
+pub mod day14;
+pub mod day15;
+pub mod day16;
+pub mod day17;
+pub mod day18;
+pub mod day19;
+pub mod day2;
+pub mod day20;
+pub mod day21;
+pub mod day22;
+pub mod day23;
+pub mod day24;
+pub mod day25;
+pub mod day3;
+pub mod day4;
+pub mod day5;
+pub mod day6;
+pub mod day7;
+pub mod day8;
+pub mod day9;
+
+/// Registers both parts of each day
+macro_rules! register {
+    ($registry:ident; $($day:literal => $module:ident),* $(,)?) => {
+        $(
+            $registry.register($day, Part::One, $module::part1);
+            $registry.register($day, Part::Two, $module::part2);
+        )*
+    };
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    register!(registry;
+        1 => day1, 2 => day2, 3 => day3, 4 => day4, 5 => day5,
+        6 => day6, 7 => day7, 8 => day8, 9 => day9, 10 => day10,
+        11 => day11, 12 => day12, 13 => day13, 14 => day14, 15 => day15,
+        16 => day16, 17 => day17, 18 => day18, 19 => day19, 20 => day20,
+        21 => day21, 22 => day22, 23 => day23, 24 => day24,
+    );
+    // Day 25 only has one part
+    registry.register(25, Part::One, day25::part1);
+}
@@ -0,0 +1,122 @@
+use std::{error::Error, fmt, str::FromStr};
+
+/// Solvers marked with `#[aoc]` must spell out `Result<T, ParseError>`,
+/// since aoc-runner only recognises return types named `Result`.
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// An error found while parsing a day's puzzle input.
+/// Lines and columns start from 1, and are 0 if the position couldn't be determined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub day: u8,
+    pub line: usize,
+    pub column: usize,
+    /// The offending part of the input
+    pub text: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "day {}, line {}, column {}: {} (found {:?})",
+            self.day, self.line, self.column, self.reason, self.text
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// The input of a day, used to point errors to the right line and column
+#[derive(Clone, Copy)]
+pub struct Source<'a> {
+    day: u8,
+    input: &'a str,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(day: u8, input: &'a str) -> Source<'a> {
+        Source { day, input }
+    }
+
+    /// Builds an error for `fragment`.
+    /// If `fragment` is a slice of the input, its position is computed from the pointer offset.
+    pub fn error(&self, fragment: &str, reason: impl Into<String>) -> ParseError {
+        let start = self.input.as_ptr() as usize;
+        let pos = fragment.as_ptr() as usize;
+        let (line, column) = if pos >= start && pos + fragment.len() <= start + self.input.len() {
+            let before = &self.input[..pos - start];
+            let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        } else {
+            (0, 0)
+        };
+        ParseError {
+            day: self.day,
+            line,
+            column,
+            text: fragment.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Parses `fragment`, turning failures into a [`ParseError`]
+    pub fn parse<T: FromStr>(&self, fragment: &str) -> ParseResult<T>
+    where
+        T::Err: fmt::Display,
+    {
+        fragment
+            .parse()
+            .map_err(|e: T::Err| self.error(fragment, e.to_string()))
+    }
+
+    /// Returns `Some` value or an error saying that the input ended too early, pointing after `after`
+    pub fn expect<T>(&self, value: Option<T>, after: &str, what: &str) -> ParseResult<T> {
+        value.ok_or_else(|| self.error(&after[after.len()..], format!("expected {}", what)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_position() {
+        let input = "abc\ndef\nghi";
+        let src = Source::new(1, input);
+        let err = src.error(&input[9..10], "bad");
+        assert_eq!((err.line, err.column, err.text.as_str()), (3, 2, "h"));
+        let err = src.error(&input[0..3], "bad");
+        assert_eq!((err.line, err.column), (1, 1));
+        let err = src.error(&String::from("abc"), "bad");
+        assert_eq!((err.line, err.column), (0, 0));
+    }
+
+    #[test]
+    fn parse_number() {
+        let input = "12\nx3";
+        let src = Source::new(1, input);
+        assert_eq!(src.parse::<u8>(&input[..2]), Ok(12));
+        let err = src.parse::<u8>(&input[3..]).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(
+            err.to_string(),
+            "day 1, line 2, column 1: invalid digit found in string (found \"x3\")"
+        );
+    }
+
+    #[test]
+    fn expect_end() {
+        let input = "abc\n";
+        let src = Source::new(1, input);
+        let err = src.expect(None::<()>, &input[..3], "a value").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.reason.as_str()),
+            (1, 4, "expected a value")
+        );
+    }
+}
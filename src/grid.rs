@@ -0,0 +1,370 @@
+//! A rectangular grid of cells, like the maps of days 3, 11 and 20.
+//!
+//! Positions are `(x, y)` with `(0, 0)` in the top left corner. Lookups take signed coordinates,
+//! so walking off the grid just returns `None` (or wraps around, see [`Wrap`]).
+
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+use crate::error::{ParseResult, Source};
+
+/// Up, right, down, left
+pub const DIRECTIONS4: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+/// The 4 directions, plus the diagonals
+pub const DIRECTIONS8: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Which edges of the grid lead back to the opposite side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    None,
+    /// Like the slopes of day 3, where the map repeats to the right
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    /// Row by row
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    /// Builds a grid from its rows, or returns `None` if they don't all have the same length
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Option<Grid<T>> {
+        let width = rows.first().map_or(0, |r| r.len());
+        if rows.iter().any(|r| r.len() != width) {
+            return None;
+        }
+        Some(Grid {
+            width,
+            height: rows.len(),
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Parses a character map, converting each character with `cell`.
+    /// Lines are trimmed, and must all have the same length.
+    /// `expected` describes the valid characters, for the error message.
+    pub fn parse<F>(src: &Source, input: &str, expected: &str, cell: F) -> ParseResult<Grid<T>>
+    where
+        F: Fn(char) -> Option<T>,
+    {
+        let mut rows = vec![];
+        for line in input.lines() {
+            let line = line.trim();
+            let row = line
+                .char_indices()
+                .map(|(i, c)| {
+                    cell(c).ok_or_else(|| {
+                        src.error(&line[i..i + c.len_utf8()], format!("expected {}", expected))
+                    })
+                })
+                .collect::<ParseResult<Vec<T>>>()?;
+            if let Some(first) = rows.first().map(Vec::len) {
+                if row.len() != first {
+                    return Err(src.error(line, format!("expected {} columns", first)));
+                }
+            }
+            rows.push(row);
+        }
+        Ok(Grid::from_rows(rows).unwrap())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index_of(&self, x: isize, y: isize, wrap: Wrap) -> Option<usize> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        let (w, h) = (self.width as isize, self.height as isize);
+        let x = match wrap {
+            Wrap::Horizontal | Wrap::Both => x.rem_euclid(w),
+            _ => x,
+        };
+        let y = match wrap {
+            Wrap::Vertical | Wrap::Both => y.rem_euclid(h),
+            _ => y,
+        };
+        if x < 0 || y < 0 || x >= w || y >= h {
+            None
+        } else {
+            Some(y as usize * self.width + x as usize)
+        }
+    }
+
+    pub fn get(&self, x: isize, y: isize) -> Option<&T> {
+        self.get_wrapping(x, y, Wrap::None)
+    }
+
+    pub fn get_mut(&mut self, x: isize, y: isize) -> Option<&mut T> {
+        let idx = self.index_of(x, y, Wrap::None)?;
+        Some(&mut self.cells[idx])
+    }
+
+    /// Like [`Grid::get`], but going past the wrapping edges comes back from the other side
+    pub fn get_wrapping(&self, x: isize, y: isize, wrap: Wrap) -> Option<&T> {
+        self.index_of(x, y, wrap).map(|idx| &self.cells[idx])
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    /// Every position, row by row
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    /// The positions next to `(x, y)` in the given directions (usually [`DIRECTIONS4`] or [`DIRECTIONS8`])
+    /// that are inside the grid
+    pub fn neighbours<'a>(
+        &'a self,
+        x: usize,
+        y: usize,
+        directions: &'a [(isize, isize)],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        directions.iter().filter_map(move |&(dx, dy)| {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            self.get(nx, ny).map(|_| (nx as usize, ny as usize))
+        })
+    }
+
+    /// Walks from `(x, y)` (excluded) in a direction, and returns the first position whose cell matches `stop`.
+    /// Returns `None` if the edge of the grid comes first.
+    pub fn cast<F>(
+        &self,
+        x: usize,
+        y: usize,
+        (dx, dy): (isize, isize),
+        stop: F,
+    ) -> Option<(usize, usize)>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let (mut x, mut y) = (x as isize, y as isize);
+        loop {
+            x += dx;
+            y += dy;
+            if stop(self.get(x, y)?) {
+                return Some((x as usize, y as usize));
+            }
+        }
+    }
+
+    pub fn count<F>(&self, f: F) -> usize
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.cells.iter().filter(|c| f(c)).count()
+    }
+
+    pub fn map<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: Fn(&T) -> U,
+    {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    /// Draws the grid, one line per row
+    pub fn render<F>(&self, f: F) -> String
+    where
+        F: Fn(&T) -> char,
+    {
+        let mut res = String::with_capacity((self.width + 1) * self.height);
+        for row in self.rows() {
+            res.extend(row.iter().map(&f));
+            res.push('\n');
+        }
+        res
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Grid<T> {
+        Grid {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+
+    pub fn column(&self, x: usize) -> Vec<T> {
+        (0..self.height)
+            .map(|y| self.cells[y * self.width + x].clone())
+            .collect()
+    }
+
+    /// Builds a `width` x `height` grid, copying each cell from the position `cell` maps it to
+    fn rebuild<F>(&self, width: usize, height: usize, cell: F) -> Grid<T>
+    where
+        F: Fn(usize, usize) -> (usize, usize),
+    {
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (x, y) = cell(x, y);
+                self.cells[y * self.width + x].clone()
+            })
+            .collect();
+        Grid {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub fn rotate_clockwise(&self) -> Grid<T> {
+        let h = self.height;
+        self.rebuild(self.height, self.width, |x, y| (y, h - 1 - x))
+    }
+
+    /// Mirrors the grid left to right
+    pub fn flip_horizontal(&self) -> Grid<T> {
+        let w = self.width;
+        self.rebuild(self.width, self.height, |x, y| (w - 1 - x, y))
+    }
+
+    /// Mirrors the grid top to bottom
+    pub fn flip_vertical(&self) -> Grid<T> {
+        let h = self.height;
+        self.rebuild(self.width, self.height, |x, y| (x, h - 1 - y))
+    }
+
+    /// Every rotation of the grid, each right after its mirrored version
+    pub fn orientations(&self) -> Vec<Grid<T>> {
+        let mut res = Vec::with_capacity(8);
+        let mut current = self.clone();
+        for _ in 0..4 {
+            res.push(current.flip_horizontal());
+            let next = current.rotate_clockwise();
+            res.push(current);
+            current = next;
+        }
+        res
+    }
+
+    /// The `width` x `height` part of the grid starting at `(x, y)`
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Grid<T> {
+        self.rebuild(width, height, |cx, cy| (x + cx, y + cy))
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(x < self.width, "x out of bounds");
+        &self.cells[y * self.width + x]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(x < self.width, "x out of bounds");
+        &mut self.cells[y * self.width + x]
+    }
+}
+
+impl fmt::Display for Grid<char> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(|&c| c))
+    }
+}
+
+/// Draws `true` as `#` and `false` as `.`
+impl fmt::Display for Grid<bool> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(|&b| if b { '#' } else { '.' }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Grid<char> {
+        let input = "ab.\n.#c";
+        Grid::parse(&Source::new(0, input), input, "anything", Some).unwrap()
+    }
+
+    #[test]
+    fn access() {
+        let grid = example();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get(1, 1), Some(&'#'));
+        assert_eq!(grid.get(3, 0), None);
+        assert_eq!(grid.get_wrapping(3, 0, Wrap::Horizontal), Some(&'a'));
+        assert_eq!(grid.get_wrapping(3, 2, Wrap::Horizontal), None);
+        assert_eq!(grid.get_wrapping(-1, -1, Wrap::Both), Some(&'c'));
+        assert_eq!(grid.column(2), vec!['.', 'c']);
+        let neighbours: Vec<_> = grid.neighbours(0, 0, &DIRECTIONS8).collect();
+        assert_eq!(neighbours, vec![(1, 0), (0, 1), (1, 1)]);
+        assert_eq!(grid.neighbours(1, 1, &DIRECTIONS4).count(), 3);
+        assert_eq!(grid.cast(0, 0, (1, 0), |&c| c == '.'), Some((2, 0)));
+        assert_eq!(grid.cast(0, 0, (1, 1), |&c| c == 'a'), None);
+    }
+
+    #[test]
+    fn transforms() {
+        let grid = example();
+        assert_eq!(grid.rotate_clockwise().to_string(), ".a\n#b\nc.\n");
+        assert_eq!(grid.flip_horizontal().to_string(), ".ba\nc#.\n");
+        assert_eq!(grid.flip_vertical().to_string(), ".#c\nab.\n");
+        assert_eq!(grid.crop(1, 0, 2, 2).to_string(), "b.\n#c\n");
+        let orientations = grid.orientations();
+        assert_eq!(orientations.len(), 8);
+        assert_eq!(orientations[1], grid);
+        assert_eq!(orientations[5], grid.rotate_clockwise().rotate_clockwise());
+    }
+
+    #[test]
+    fn invalid_maps() {
+        let input = "..#\n.#";
+        let cell = |c| match c {
+            '.' => Some(false),
+            '#' => Some(true),
+            _ => None,
+        };
+        let err = Grid::parse(&Source::new(0, input), input, "`.` or `#`", cell).unwrap_err();
+        assert_eq!((err.line, err.reason.as_str()), (2, "expected 3 columns"));
+        let input = "..x";
+        let err = Grid::parse(&Source::new(0, input), input, "`.` or `#`", cell).unwrap_err();
+        assert_eq!(
+            (err.column, err.reason.as_str()),
+            (3, "expected `.` or `#`")
+        );
+    }
+}
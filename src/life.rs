@@ -0,0 +1,389 @@
+//! Cellular automata in the style of Conway's Game of Life, as seen in days 11, 17 and 24.
+//!
+//! Only the live cells are stored, so unbounded topologies work as long as the population stays finite.
+//! Each generation counts the live neighbours of every cell next to a live one,
+//! then applies the birth/survive [`Rule`] to them.
+
+use std::{hash::Hash, str::FromStr};
+
+use fxhash::{FxHashMap, FxHashSet};
+
+use crate::grid::{Grid, DIRECTIONS8};
+
+/// How cells are laid out: which cells exist, and which ones are next to each other
+pub trait Topology {
+    type Cell: Copy + Eq + Hash;
+
+    fn for_each_neighbour<F: FnMut(Self::Cell)>(&self, cell: Self::Cell, f: F);
+
+    /// Every cell, if there are finitely many.
+    /// Rules that bring cells to life without any live neighbour need this.
+    fn cells(&self) -> Option<Vec<Self::Cell>> {
+        None
+    }
+}
+
+/// An unbounded N-dimensional grid, where each cell has 3^N - 1 neighbours (diagonals included)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Square<const N: usize>;
+
+impl<const N: usize> Topology for Square<N> {
+    type Cell = [i32; N];
+
+    fn for_each_neighbour<F: FnMut([i32; N])>(&self, cell: [i32; N], mut f: F) {
+        for mut i in 0..3usize.pow(N as u32) {
+            let mut neighbour = cell;
+            for coord in neighbour.iter_mut() {
+                *coord += (i % 3) as i32 - 1;
+                i /= 3;
+            }
+            if neighbour != cell {
+                f(neighbour);
+            }
+        }
+    }
+}
+
+/// Axial coordinates, see https://www.redblobgames.com/grids/hexagons/#coordinates-axial
+pub const HEX_DIRECTIONS: [(i32, i32); 6] = [(1, -1), (0, -1), (0, 1), (-1, 1), (1, 0), (-1, 0)];
+
+/// An unbounded grid of hexagons in axial coordinates, where each cell has 6 neighbours
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hex;
+
+impl Topology for Hex {
+    type Cell = (i32, i32);
+
+    fn for_each_neighbour<F: FnMut((i32, i32))>(&self, (q, r): (i32, i32), mut f: F) {
+        for (dq, dr) in HEX_DIRECTIONS.iter() {
+            f((q + dq, r + dr));
+        }
+    }
+}
+
+/// The seats of a waiting area (day 11). Cells are seat indices, floor isn't a cell.
+#[derive(Debug, Clone)]
+pub struct Seats {
+    positions: Vec<(usize, usize)>,
+    index: Grid<Option<usize>>,
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl Seats {
+    /// `seats` is `true` where there's a seat. Each seat's neighbours are the seats right next to it,
+    /// or the first seat in each of the 8 directions if `far`.
+    pub fn new(seats: &Grid<bool>, far: bool) -> Seats {
+        let mut index = seats.map(|_| None);
+        let mut positions = vec![];
+        for pos in seats.positions() {
+            if seats[pos] {
+                index[pos] = Some(positions.len());
+                positions.push(pos);
+            }
+        }
+        let neighbours = positions
+            .iter()
+            .map(|&(x, y)| {
+                DIRECTIONS8
+                    .iter()
+                    .filter_map(|&(dx, dy)| {
+                        if far {
+                            seats.cast(x, y, (dx, dy), |&s| s)
+                        } else {
+                            let (nx, ny) = (x as isize + dx, y as isize + dy);
+                            seats
+                                .get(nx, ny)
+                                .filter(|&&s| s)
+                                .map(|_| (nx as usize, ny as usize))
+                        }
+                    })
+                    .map(|pos| index[pos].unwrap())
+                    .collect()
+            })
+            .collect();
+        Seats {
+            positions,
+            index,
+            neighbours,
+        }
+    }
+
+    /// The index of the seat at `(x, y)`, if there is one
+    pub fn index_of(&self, x: usize, y: usize) -> Option<usize> {
+        self.index[(x, y)]
+    }
+
+    pub fn position(&self, seat: usize) -> (usize, usize) {
+        self.positions[seat]
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The seats next to this one, computed once in [`Seats::new`]
+    pub fn neighbours(&self, seat: usize) -> &[usize] {
+        &self.neighbours[seat]
+    }
+}
+
+impl Topology for Seats {
+    type Cell = usize;
+
+    fn for_each_neighbour<F: FnMut(usize)>(&self, cell: usize, f: F) {
+        self.neighbours[cell].iter().copied().for_each(f);
+    }
+
+    fn cells(&self) -> Option<Vec<usize>> {
+        Some((0..self.positions.len()).collect())
+    }
+}
+
+/// Which live neighbour counts bring a dead cell to life, and which ones keep a live cell alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    birth: Vec<usize>,
+    survive: Vec<usize>,
+}
+
+impl Rule {
+    pub fn new(birth: &[usize], survive: &[usize]) -> Rule {
+        Rule {
+            birth: birth.to_vec(),
+            survive: survive.to_vec(),
+        }
+    }
+
+    /// B3/S23, which is also the rule of day 17
+    pub fn conway() -> Rule {
+        Rule::new(&[3], &[2, 3])
+    }
+
+    pub fn next(&self, alive: bool, neighbours: usize) -> bool {
+        if alive {
+            self.survive.contains(&neighbours)
+        } else {
+            self.birth.contains(&neighbours)
+        }
+    }
+}
+
+/// Parses the usual `B<counts>/S<counts>` notation, like `B3/S23`. Every count is a single digit.
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rule, String> {
+        let counts = |part: &str, prefix: char| {
+            part.strip_prefix(prefix)
+                .ok_or_else(|| format!("expected `{}`", prefix))?
+                .chars()
+                .map(|c| {
+                    c.to_digit(10)
+                        .map(|d| d as usize)
+                        .ok_or_else(|| format!("`{}` isn't a digit", c))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let (birth, survive) = s.split_once('/').ok_or("expected `B<counts>/S<counts>`")?;
+        Ok(Rule {
+            birth: counts(birth, 'B')?,
+            survive: counts(survive, 'S')?,
+        })
+    }
+}
+
+/// A running automaton. Iterating over it runs one generation per item, yielding the live cells.
+#[derive(Debug, Clone)]
+pub struct Life<T: Topology> {
+    topology: T,
+    rule: Rule,
+    alive: FxHashSet<T::Cell>,
+    generation: usize,
+}
+
+impl<T: Topology> Life<T> {
+    pub fn new<I>(topology: T, rule: Rule, alive: I) -> Life<T>
+    where
+        I: IntoIterator<Item = T::Cell>,
+    {
+        assert!(
+            !rule.birth.contains(&0) || topology.cells().is_some(),
+            "cells can only be born without neighbours in a finite topology"
+        );
+        Life {
+            topology,
+            rule,
+            alive: alive.into_iter().collect(),
+            generation: 0,
+        }
+    }
+
+    pub fn topology(&self) -> &T {
+        &self.topology
+    }
+
+    pub fn alive(&self) -> &FxHashSet<T::Cell> {
+        &self.alive
+    }
+
+    pub fn population(&self) -> usize {
+        self.alive.len()
+    }
+
+    /// How many generations ran so far
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn live_neighbours(&self, cell: T::Cell) -> usize {
+        let mut count = 0;
+        self.topology.for_each_neighbour(cell, |n| {
+            if self.alive.contains(&n) {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Runs a single generation, returning whether any cell changed
+    pub fn step(&mut self) -> bool {
+        let mut counts: FxHashMap<T::Cell, usize> = FxHashMap::default();
+        for &cell in &self.alive {
+            self.topology
+                .for_each_neighbour(cell, |n| *counts.entry(n).or_insert(0) += 1);
+        }
+        let mut next = FxHashSet::default();
+        let mut visit = |cell: T::Cell| {
+            let neighbours = counts.get(&cell).copied().unwrap_or(0);
+            if self.rule.next(self.alive.contains(&cell), neighbours) {
+                next.insert(cell);
+            }
+        };
+        match self.topology.cells() {
+            Some(cells) => cells.into_iter().for_each(visit),
+            None => {
+                // Live cells without live neighbours aren't in `counts`
+                self.alive
+                    .iter()
+                    .filter(|c| !counts.contains_key(c))
+                    .for_each(|&c| visit(c));
+                counts.keys().for_each(|&c| visit(c));
+            }
+        }
+        self.generation += 1;
+        let changed = next != self.alive;
+        self.alive = next;
+        changed
+    }
+
+    pub fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    /// Runs until a generation doesn't change anything, and returns how many generations did.
+    /// Patterns that never settle give `None`: either the live cells come back to an earlier
+    /// arrangement (a blinker), or `limit` generations ran without settling (a glider, which never repeats).
+    /// The automaton is left wherever it stopped.
+    pub fn stabilize(&mut self, limit: usize) -> Option<usize> {
+        let start = self.generation;
+        // Brent's cycle detection: compare against a snapshot taken at each power of two
+        let mut earlier = self.alive.clone();
+        let (mut power, mut steps) = (1, 0);
+        while self.generation - start < limit {
+            if !self.step() {
+                return Some(self.generation - start - 1);
+            }
+            if self.alive == earlier {
+                return None;
+            }
+            steps += 1;
+            if steps == power {
+                earlier.clone_from(&self.alive);
+                power *= 2;
+                steps = 0;
+            }
+        }
+        None
+    }
+}
+
+impl<T: Topology> Iterator for Life<T> {
+    type Item = FxHashSet<T::Cell>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step();
+        Some(self.alive.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blinker() {
+        let mut life = Life::new(Square::<2>, Rule::conway(), vec![[0, -1], [0, 0], [0, 1]]);
+        let horizontal: FxHashSet<_> = vec![[-1, 0], [0, 0], [1, 0]].into_iter().collect();
+        assert_eq!(life.next(), Some(horizontal));
+        assert_eq!(life.nth(1).map(|cells| cells.len()), Some(3));
+        assert_eq!(life.generation(), 3);
+        assert_eq!(life.live_neighbours([0, 0]), 2);
+        assert_eq!(life.live_neighbours([0, 1]), 3);
+    }
+
+    #[test]
+    fn unsettled() {
+        let mut blinker = Life::new(Square::<2>, Rule::conway(), vec![[0, -1], [0, 0], [0, 1]]);
+        assert_eq!(blinker.stabilize(1000), None);
+        assert!(blinker.generation() <= 4);
+
+        let glider = vec![[1, 0], [2, 1], [0, 2], [1, 2], [2, 2]];
+        let mut glider = Life::new(Square::<2>, Rule::conway(), glider);
+        assert_eq!(glider.stabilize(100), None);
+        assert_eq!(glider.generation(), 100);
+
+        let mut block = Life::new(
+            Square::<2>,
+            Rule::conway(),
+            vec![[0, 0], [0, 1], [1, 0], [1, 1]],
+        );
+        assert_eq!(block.stabilize(0), None);
+        assert_eq!(block.stabilize(1), Some(0));
+    }
+
+    #[test]
+    fn rules() {
+        assert_eq!("B3/S23".parse(), Ok(Rule::conway()));
+        assert_eq!("B36/S".parse(), Ok(Rule::new(&[3, 6], &[])));
+        assert!("S23/B3".parse::<Rule>().is_err());
+        assert!("B3x/S23".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn seats() {
+        // #.#
+        // .##
+        let grid = Grid::from_rows(vec![vec![true, false, true], vec![false, true, true]]).unwrap();
+        let near = Seats::new(&grid, false);
+        assert_eq!(near.index_of(1, 1), Some(2));
+        assert_eq!(near.position(3), (2, 1));
+        let mut counts = vec![];
+        near.for_each_neighbour(0, |n| counts.push(n));
+        assert_eq!(counts, vec![2]);
+        let far = Seats::new(&grid, true);
+        counts.clear();
+        far.for_each_neighbour(0, |n| counts.push(n));
+        assert_eq!(counts, vec![1, 2]);
+        // Every seat fills up, then the crowded ones empty
+        let mut life = Life::new(far, Rule::new(&[0], &[0, 1, 2]), vec![]);
+        assert!(life.step());
+        assert_eq!(life.population(), 4);
+        assert_eq!(life.stabilize(10), Some(1));
+    }
+}
@@ -1,4 +1,265 @@
-extern crate aoc_2020;
-use aoc_runner_derive::aoc_main;
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process, thread,
+};
 
-aoc_main! { lib = aoc_2020 }
+use aoc_2020::{
+    bench::{self, Report},
+    runner::{self, Answers, Run, Status},
+    solver::{self, Part},
+};
+
+const USAGE: &str = "usage: aoc_2020 [bench] [options]
+
+    -d, --day <N>           run a single day (can be repeated)
+    -p, --part <1|2>        only run one part of each day
+    -a, --all               run every day (the default)
+    -i, --input <DIR>       look for the inputs in DIR (default: input)
+        --stdin             read the input from stdin, for a single day
+        --json              print the report as JSON
+        --answers <FILE>    check the answers against FILE (default: answers.toml, if it exists)
+        --save-answers <FILE>
+                            write the answers to FILE
+    -h, --help              print this message
+
+bench options:
+    -n, --iterations <N>    run each part N times (default: 10)
+    -t, --threads <N>       run N parts at once (default: one per core)
+        --csv               print the report as CSV
+        --baseline <FILE>   compare with an earlier JSON or CSV report
+        --threshold <PCT>   fail if a part got slower than the baseline by PCT% (default: 10)";
+
+struct Options {
+    bench: bool,
+    days: Vec<u8>,
+    part: Option<Part>,
+    input: Option<PathBuf>,
+    stdin: bool,
+    json: bool,
+    answers: Option<PathBuf>,
+    save_answers: Option<PathBuf>,
+    iterations: usize,
+    threads: usize,
+    csv: bool,
+    baseline: Option<PathBuf>,
+    threshold: f64,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            bench: false,
+            days: vec![],
+            part: None,
+            input: None,
+            stdin: false,
+            json: false,
+            answers: None,
+            save_answers: None,
+            iterations: 10,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            csv: false,
+            baseline: None,
+            threshold: 10.0,
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?} for {}", value, name))
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.peekable();
+    if args.peek().map(String::as_str) == Some("bench") {
+        options.bench = true;
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "-d" | "--day" => options.days.push(parse_number(&arg, &value(&arg)?)?),
+            "-p" | "--part" => options.part = Some(value(&arg)?.parse()?),
+            "-a" | "--all" => options.days.clear(),
+            "-i" | "--input" => options.input = Some(value(&arg)?.into()),
+            "--stdin" => options.stdin = true,
+            "--json" => options.json = true,
+            "--answers" => options.answers = Some(value(&arg)?.into()),
+            "--save-answers" => options.save_answers = Some(value(&arg)?.into()),
+            "-n" | "--iterations" => options.iterations = parse_number(&arg, &value(&arg)?)?,
+            "-t" | "--threads" => options.threads = parse_number(&arg, &value(&arg)?)?,
+            "--csv" => options.csv = true,
+            "--baseline" => options.baseline = Some(value(&arg)?.into()),
+            "--threshold" => options.threshold = parse_number(&arg, &value(&arg)?)?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown argument {:?}", arg)),
+        }
+    }
+    if options.stdin && options.days.len() != 1 {
+        return Err("--stdin needs exactly one --day".to_string());
+    }
+    Ok(options)
+}
+
+fn load_answers(options: &Options) -> Result<Answers, String> {
+    let path = match &options.answers {
+        Some(path) => path.as_path(),
+        None if Path::new("answers.toml").is_file() => Path::new("answers.toml"),
+        None => return Ok(Answers::default()),
+    };
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    Answers::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn read_input(options: &Options, day: u8) -> Result<String, String> {
+    if options.stdin {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| format!("can't read stdin: {}", e))?;
+        return Ok(input.trim_end().to_string());
+    }
+    let dir = options
+        .input
+        .as_deref()
+        .unwrap_or_else(|| Path::new("input"));
+    let path = runner::find_input(dir, day)
+        .ok_or_else(|| format!("no input for day {} in {}", day, dir.display()))?;
+    let input =
+        fs::read_to_string(&path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    // Like cargo-aoc, which drops the final newline
+    Ok(input.trim_end().to_string())
+}
+
+/// Reads the input of every selected part that has one
+fn load_inputs(options: &Options) -> Result<Vec<(u8, Part, String)>, String> {
+    let puzzles: Vec<(u8, Part)> = solver::registry()
+        .puzzles()
+        .filter(|(day, _)| options.days.is_empty() || options.days.contains(day))
+        .filter(|(_, part)| options.part.is_none_or(|p| p == *part))
+        .collect();
+    if puzzles.is_empty() {
+        return Err("nothing to run".to_string());
+    }
+
+    let mut inputs: Vec<(u8, Part, String)> = vec![];
+    let mut input: Option<(u8, Option<String>)> = None;
+    for (day, part) in puzzles {
+        // Both parts share the input, and stdin can only be read once
+        if input.as_ref().is_none_or(|(d, _)| *d != day) {
+            let content = read_input(options, day)
+                .map_err(|e| eprintln!("{}, skipping", e))
+                .ok();
+            input = Some((day, content));
+        }
+        if let Some((_, Some(content))) = &input {
+            inputs.push((day, part, content.clone()));
+        }
+    }
+    if inputs.is_empty() {
+        return Err("none of the inputs were found".to_string());
+    }
+    Ok(inputs)
+}
+
+fn run(options: &Options) -> Result<bool, String> {
+    let answers = load_answers(options)?;
+    let runs: Vec<Run> = load_inputs(options)?
+        .iter()
+        .map(|(day, part, input)| {
+            let expected = answers.get(*day, *part).cloned();
+            runner::run(*day, *part, input, expected)
+        })
+        .collect();
+
+    if options.json {
+        print!("{}", runner::json(&runs));
+    } else {
+        print!("{}", runner::table(&runs));
+    }
+
+    if let Some(path) = &options.save_answers {
+        let mut answers = answers;
+        for run in &runs {
+            if let Ok(answer) = &run.result {
+                answers.insert(run.day, run.part, answer.clone());
+            }
+        }
+        fs::write(path, answers.to_toml())
+            .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+    }
+    Ok(runs
+        .iter()
+        .all(|r| !matches!(r.status(), Status::Wrong | Status::Failed)))
+}
+
+fn run_bench(options: &Options) -> Result<bool, String> {
+    let baseline = match &options.baseline {
+        Some(path) => {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+            Some(Report::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?)
+        }
+        None => None,
+    };
+    let inputs = load_inputs(options)?;
+    let parts: Vec<(u8, Part, &str)> = inputs
+        .iter()
+        .map(|(day, part, input)| (*day, *part, input.as_str()))
+        .collect();
+    let report =
+        bench::run(&parts, options.iterations, options.threads).map_err(|e| e.to_string())?;
+
+    if options.json {
+        print!("{}", report.to_json());
+    } else if options.csv {
+        print!("{}", report.to_csv());
+    } else {
+        print!("{}", report.table(baseline.as_ref()));
+    }
+
+    let regressions = baseline.map_or_else(Vec::new, |b| {
+        report.regressions(&b, options.threshold / 100.0)
+    });
+    for change in &regressions {
+        eprintln!(
+            "day {} part {} got {:.1}% slower",
+            change.day,
+            change.part,
+            change.relative() * 100.0
+        );
+    }
+    Ok(regressions.is_empty())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    let result = if options.bench {
+        run_bench(&options)
+    } else {
+        run(&options)
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Runs the registered solutions like cargo-aoc would: finds the inputs, times each part,
+//! checks the results against the known answers and reports everything as a table or JSON.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{ParseResult, Source},
+    json_string,
+    solver::{self, Answer, Part, SolverError},
+};
+
+/// Looks for `day<N>.txt` in `dir`, then in `dir/2020` (where cargo-aoc downloads the inputs)
+pub fn find_input(dir: &Path, day: u8) -> Option<PathBuf> {
+    let name = format!("day{}.txt", day);
+    [dir.join(&name), dir.join("2020").join(&name)]
+        .iter()
+        .find(|p| p.is_file())
+        .cloned()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The answer matches the expected one
+    Correct,
+    Wrong,
+    /// There's no expected answer to compare with
+    Unchecked,
+    Failed,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Correct => "ok",
+            Status::Wrong => "wrong",
+            Status::Unchecked => "unchecked",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+/// The outcome of running a single part
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub day: u8,
+    pub part: Part,
+    pub result: Result<Answer, SolverError>,
+    pub elapsed: Duration,
+    pub expected: Option<Answer>,
+}
+
+impl Run {
+    pub fn status(&self) -> Status {
+        match (&self.result, &self.expected) {
+            (Err(_), _) => Status::Failed,
+            (Ok(_), None) => Status::Unchecked,
+            (Ok(answer), Some(expected)) if answer == expected => Status::Correct,
+            (Ok(_), Some(_)) => Status::Wrong,
+        }
+    }
+}
+
+/// Runs a part with the registered solution, timing it
+pub fn run(day: u8, part: Part, input: &str, expected: Option<Answer>) -> Run {
+    let start = Instant::now();
+    let result = solver::solve(day, part, input);
+    Run {
+        day,
+        part,
+        result,
+        elapsed: start.elapsed(),
+        expected,
+    }
+}
+
+/// The known answers, stored in a small subset of TOML:
+///
+/// ```toml
+/// [day1]
+/// part1 = 1014171
+/// part2 = "abc"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Answers(BTreeMap<(u8, Part), Answer>);
+
+/// Reads the escapes of a TOML basic string, which include everything [`json_string`] writes
+fn unescape(src: &Source, text: &str) -> ParseResult<String> {
+    let mut res = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            if c == '"' {
+                return Err(src.error(&text[i..i + 1], "unescaped `\"`"));
+            }
+            res.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some((_, '"')) => '"',
+            Some((_, '\\')) => '\\',
+            Some((_, 'n')) => '\n',
+            Some((_, 't')) => '\t',
+            Some((_, 'r')) => '\r',
+            Some((_, 'b')) => '\u{8}',
+            Some((_, 'f')) => '\u{c}',
+            Some((_, u @ ('u' | 'U'))) => {
+                let len = if u == 'u' { 4 } else { 8 };
+                let code = text.get(i + 2..i + 2 + len).unwrap_or("");
+                let c = u32::from_str_radix(code, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| src.error(&text[i..], "invalid unicode escape"))?;
+                chars.nth(len - 1);
+                c
+            }
+            _ => return Err(src.error(&text[i..], "invalid escape")),
+        };
+        res.push(escaped);
+    }
+    Ok(res)
+}
+
+fn parse_value(src: &Source, value: &str) -> ParseResult<Answer> {
+    match value.strip_prefix('"') {
+        Some(rest) => {
+            let text = rest
+                .strip_suffix('"')
+                .ok_or_else(|| src.error(value, "unterminated string"))?;
+            Ok(Answer::Text(unescape(src, text)?))
+        }
+        None => Ok(Answer::Number(src.parse(value)?)),
+    }
+}
+
+impl Answers {
+    pub fn parse(input: &str) -> ParseResult<Answers> {
+        let src = Source::new(0, input);
+        let mut answers = BTreeMap::new();
+        let mut day = None;
+        for line in input.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .strip_suffix(']')
+                    .and_then(|h| h.strip_prefix("day"))
+                    .ok_or_else(|| src.error(line, "expected `[day<N>]`"))?;
+                day = Some(src.parse(name)?);
+                continue;
+            }
+            let day = src.expect(day, "", "a `[day<N>]` section before the answers")?;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| src.error(line, "expected `part<N> = <answer>`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let part = key
+                .strip_prefix("part")
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(|| src.error(key, "expected `part1` or `part2`"))?;
+            answers.insert((day, part), parse_value(&src, value)?);
+        }
+        Ok(Answers(answers))
+    }
+
+    pub fn get(&self, day: u8, part: Part) -> Option<&Answer> {
+        self.0.get(&(day, part))
+    }
+
+    pub fn insert(&mut self, day: u8, part: Part, answer: Answer) {
+        self.0.insert((day, part), answer);
+    }
+
+    /// Writes the answers back in the format [`Answers::parse`] reads.
+    /// [`Answer::NoSolution`] can't be stored, so it's left out.
+    pub fn to_toml(&self) -> String {
+        let mut res = String::new();
+        let mut last_day = None;
+        for (&(day, part), answer) in &self.0 {
+            if *answer == Answer::NoSolution {
+                continue;
+            }
+            if last_day != Some(day) {
+                if last_day.is_some() {
+                    res.push('\n');
+                }
+                writeln!(res, "[day{}]", day).unwrap();
+                last_day = Some(day);
+            }
+            match answer {
+                Answer::Text(s) => writeln!(res, "part{} = {}", part, json_string(s)),
+                answer => writeln!(res, "part{} = {}", part, answer),
+            }
+            .unwrap();
+        }
+        res
+    }
+}
+
+pub(crate) fn format_duration(d: Duration) -> String {
+    let micros = d.as_secs_f64() * 1e6;
+    if micros < 1000.0 {
+        format!("{:.1} µs", micros)
+    } else if micros < 1e6 {
+        format!("{:.3} ms", micros / 1000.0)
+    } else {
+        format!("{:.3} s", micros / 1e6)
+    }
+}
+
+/// A plain text table, with one row per run and the total time at the bottom
+pub fn table(runs: &[Run]) -> String {
+    let rows: Vec<[String; 5]> = runs
+        .iter()
+        .map(|r| {
+            let answer = match &r.result {
+                Ok(answer) => answer.to_string(),
+                Err(e) => e.to_string(),
+            };
+            let expected = match (r.status(), &r.expected) {
+                (Status::Wrong, Some(expected)) => format!("expected {}", expected),
+                _ => String::new(),
+            };
+            [
+                format!("{:>2}/{}", r.day, r.part),
+                format_duration(r.elapsed),
+                r.status().name().to_string(),
+                answer,
+                expected,
+            ]
+        })
+        .collect();
+    let header = ["day", "time", "status", "answer", ""];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let mut res = String::new();
+    for row in std::iter::once(header.map(String::from)).chain(rows) {
+        let line = format!(
+            "{:<w0$}  {:>w1$}  {:<w2$}  {:<w3$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        );
+        res += line.trim_end();
+        res.push('\n');
+    }
+    let total: Duration = runs.iter().map(|r| r.elapsed).sum();
+    writeln!(res, "total: {}", format_duration(total)).unwrap();
+    res
+}
+
+fn answer_json(answer: &Answer) -> String {
+    match answer {
+        Answer::Number(n) => n.to_string(),
+        Answer::Text(s) => json_string(s),
+        Answer::NoSolution => "null".to_string(),
+    }
+}
+
+/// A JSON array with one object per run. Times are in nanoseconds.
+pub fn json(runs: &[Run]) -> String {
+    let entries: Vec<String> = runs
+        .iter()
+        .map(|r| {
+            let mut entry = format!(
+                "{{\"day\":{},\"part\":{},\"status\":\"{}\",\"time_ns\":{}",
+                r.day,
+                r.part,
+                r.status().name(),
+                r.elapsed.as_nanos()
+            );
+            match &r.result {
+                Ok(answer) => write!(entry, ",\"answer\":{}", answer_json(answer)),
+                Err(e) => write!(entry, ",\"error\":{}", json_string(&e.to_string())),
+            }
+            .unwrap();
+            if let Some(expected) = &r.expected {
+                write!(entry, ",\"expected\":{}", answer_json(expected)).unwrap();
+            }
+            entry + "}"
+        })
+        .collect();
+    format!("[{}]\n", entries.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWERS: &str = "# Checked by hand
+[day1]
+part1 = 514579
+part2 = 241861950
+
+[day23]
+part1 = \"67384529\"
+";
+
+    #[test]
+    fn answers_file() {
+        let answers = Answers::parse(ANSWERS).unwrap();
+        assert_eq!(answers.get(1, Part::Two), Some(&Answer::Number(241861950)));
+        assert_eq!(
+            answers.get(23, Part::One),
+            Some(&Answer::Text("67384529".to_string()))
+        );
+        assert_eq!(answers.get(23, Part::Two), None);
+        assert_eq!(Answers::parse(&answers.to_toml()), Ok(answers));
+        let err = Answers::parse("part1 = 3").unwrap_err();
+        assert_eq!(
+            err.reason,
+            "expected a `[day<N>]` section before the answers"
+        );
+        let err = Answers::parse("[day1]\npart3 = 3").unwrap_err();
+        assert_eq!((err.line, err.text.as_str()), (2, "part3"));
+    }
+
+    #[test]
+    fn escaped_answers() {
+        let mut answers = Answers::default();
+        answers.insert(1, Part::One, Answer::from("say \"hi\" \\ bye\n\u{1}é"));
+        let toml = answers.to_toml();
+        assert_eq!(
+            toml,
+            "[day1]\npart1 = \"say \\\"hi\\\" \\\\ bye\\u000a\\u0001é\"\n"
+        );
+        assert_eq!(Answers::parse(&toml), Ok(answers));
+        let answers = Answers::parse("[day1]\npart1 = \"a\\tb\\U0001F600\"").unwrap();
+        assert_eq!(
+            answers.get(1, Part::One),
+            Some(&Answer::from("a\tb\u{1F600}"))
+        );
+        assert!(Answers::parse("[day1]\npart1 = \"a\"b\"").is_err());
+        assert!(Answers::parse("[day1]\npart1 = \"a\\\"").is_err());
+        assert!(Answers::parse("[day1]\npart1 = \"\\x\"").is_err());
+    }
+
+    #[test]
+    fn reports() {
+        let input = example!(1721 979 366 299 675 1456);
+        let runs = vec![
+            run(1, Part::One, input, Some(Answer::Number(514579))),
+            run(1, Part::Two, input, Some(Answer::Number(1))),
+            run(1, Part::One, "x", None),
+        ];
+        let statuses: Vec<_> = runs.iter().map(Run::status).collect();
+        assert_eq!(statuses, [Status::Correct, Status::Wrong, Status::Failed]);
+        let table = table(&runs);
+        let row: Vec<_> = table.lines().nth(2).unwrap().split_whitespace().collect();
+        assert_eq!(
+            row[row.len() - 4..],
+            ["wrong", "241861950", "expected", "1"]
+        );
+        let json = json(&runs);
+        assert!(json.starts_with("[{\"day\":1,\"part\":1,\"status\":\"ok\",\"time_ns\":"));
+        assert!(json.contains(",\"answer\":514579,\"expected\":514579}"));
+        assert!(json.contains("\"status\":\"failed\""));
+    }
+}
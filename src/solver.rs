@@ -0,0 +1,221 @@
+//! Runs any day's solution without going through aoc-runner.
+//!
+//! Every part is registered under its day and [`Part`] in the global [`registry`],
+//! so `aoc_2020::solve(13, Part::Two, &input)` works from any tool.
+
+use std::{collections::BTreeMap, error::Error, fmt, str::FromStr};
+
+use crate::error::ParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Part {
+    One,
+    Two,
+}
+
+impl Part {
+    pub fn number(self) -> u8 {
+        match self {
+            Part::One => 1,
+            Part::Two => 2,
+        }
+    }
+}
+
+/// Parses `1` or `2`
+impl FromStr for Part {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Part, String> {
+        match s {
+            "1" => Ok(Part::One),
+            "2" => Ok(Part::Two),
+            _ => Err(format!("expected part 1 or 2, found {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+/// The answer of a part. Every integer answer fits in an `i128`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Answer {
+    Number(i128),
+    Text(String),
+    /// The input has no solution, e.g. no two entries of day 1 sum to 2020
+    NoSolution,
+}
+
+macro_rules! answer_from_int {
+    ($($t:ty)*) => {
+        $(impl From<$t> for Answer {
+            fn from(n: $t) -> Answer {
+                Answer::Number(n as i128)
+            }
+        })*
+    };
+}
+
+answer_from_int!(i32 u32 i64 u64 i128 isize usize);
+
+impl From<String> for Answer {
+    fn from(s: String) -> Answer {
+        Answer::Text(s)
+    }
+}
+
+impl From<&str> for Answer {
+    fn from(s: &str) -> Answer {
+        Answer::Text(s.to_string())
+    }
+}
+
+/// `None` becomes [`Answer::NoSolution`]
+impl<T: Into<Answer>> From<Option<T>> for Answer {
+    fn from(answer: Option<T>) -> Answer {
+        answer.map_or(Answer::NoSolution, Into::into)
+    }
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Answer::Number(n) => write!(f, "{}", n),
+            Answer::Text(s) => write!(f, "{}", s),
+            Answer::NoSolution => write!(f, "no solution"),
+        }
+    }
+}
+
+/// Solves one part of a day, given the puzzle input
+pub trait Solver: Send + Sync {
+    fn solve(&self, input: &str) -> Result<Answer, ParseError>;
+}
+
+impl<F> Solver for F
+where
+    F: Fn(&str) -> Result<Answer, ParseError> + Send + Sync,
+{
+    fn solve(&self, input: &str) -> Result<Answer, ParseError> {
+        self(input)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolverError {
+    /// Nothing is registered for this day and part
+    UnknownPuzzle {
+        day: u8,
+        part: Part,
+    },
+    Parse(ParseError),
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverError::UnknownPuzzle { day, part } => {
+                write!(f, "no solution for day {} part {}", day, part)
+            }
+            SolverError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SolverError {}
+
+impl From<ParseError> for SolverError {
+    fn from(e: ParseError) -> SolverError {
+        SolverError::Parse(e)
+    }
+}
+
+/// Solvers keyed by day and part
+#[derive(Default)]
+pub struct Registry {
+    solvers: BTreeMap<(u8, Part), Box<dyn Solver>>,
+}
+
+impl Registry {
+    /// Adds a solver, replacing any other one for the same day and part
+    pub fn register<S: Solver + 'static>(&mut self, day: u8, part: Part, solver: S) {
+        self.solvers.insert((day, part), Box::new(solver));
+    }
+
+    pub fn get(&self, day: u8, part: Part) -> Option<&dyn Solver> {
+        self.solvers.get(&(day, part)).map(|s| s.as_ref())
+    }
+
+    /// Every registered day and part, in order
+    pub fn puzzles(&self) -> impl Iterator<Item = (u8, Part)> + '_ {
+        self.solvers.keys().copied()
+    }
+
+    pub fn solve(&self, day: u8, part: Part, input: &str) -> Result<Answer, SolverError> {
+        let solver = self
+            .get(day, part)
+            .ok_or(SolverError::UnknownPuzzle { day, part })?;
+        Ok(solver.solve(input)?)
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Registry = {
+        let mut registry = Registry::default();
+        crate::days::register(&mut registry);
+        registry
+    };
+}
+
+/// The solutions of every day
+pub fn registry() -> &'static Registry {
+    &REGISTRY
+}
+
+/// Solves a part of a day with the registered solution
+pub fn solve(day: u8, part: Part, input: &str) -> Result<Answer, SolverError> {
+    registry().solve(day, part, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_days() {
+        assert_eq!(registry().puzzles().count(), 49);
+        assert_eq!(
+            solve(13, Part::Two, "939\n7,13,x,x,59,x,31,19"),
+            Ok(Answer::Number(1068781))
+        );
+        assert_eq!(
+            solve(23, Part::One, "389125467"),
+            Ok(Answer::Text("67384529".to_string()))
+        );
+        assert_eq!(
+            solve(25, Part::Two, ""),
+            Err(SolverError::UnknownPuzzle {
+                day: 25,
+                part: Part::Two
+            })
+        );
+        assert!(matches!(
+            solve(1, Part::One, "12\nabc"),
+            Err(SolverError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn custom_registry() {
+        let mut registry = Registry::default();
+        registry.register(1, Part::One, |input: &str| Ok(Answer::from(input.len())));
+        assert_eq!(registry.solve(1, Part::One, "abc"), Ok(Answer::Number(3)));
+        assert_eq!(Answer::Text("x".to_string()).to_string(), "x");
+        assert_eq!(Answer::from(Some(0)), Answer::Number(0));
+        assert_eq!(Answer::from(None::<i32>).to_string(), "no solution");
+    }
+}
@@ -0,0 +1,114 @@
+//! Tokens for the small expression languages of the days, like the password policies of day 2
+//! and the group queries of day 6: words, numbers, quoted strings and `(`, `)`, `,`, `=`.
+
+use crate::error::{ParseError, ParseResult, Source};
+
+/// The tokens of a line, read from left to right
+pub(crate) struct Tokens<'a, 's> {
+    src: &'s Source<'a>,
+    line: &'a str,
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a, 's> Tokens<'a, 's> {
+    pub(crate) fn new(src: &'s Source<'a>, line: &'a str) -> ParseResult<Tokens<'a, 's>> {
+        let mut tokens = vec![];
+        let mut rest = line.trim_start();
+        while let Some(c) = rest.chars().next() {
+            let len = match c {
+                '(' | ')' | ',' | '=' => 1,
+                '"' => {
+                    let end = rest[1..]
+                        .find('"')
+                        .ok_or_else(|| src.error(rest, "unterminated string"))?;
+                    end + 2
+                }
+                c if c.is_alphanumeric() || c == '_' => rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len()),
+                c => return Err(src.error(&rest[..c.len_utf8()], "unexpected character")),
+            };
+            tokens.push(&rest[..len]);
+            rest = rest[len..].trim_start();
+        }
+        Ok(Tokens {
+            src,
+            line,
+            tokens,
+            pos: 0,
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub(crate) fn src(&self) -> &'s Source<'a> {
+        self.src
+    }
+
+    pub(crate) fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    /// Skips the next token if it's `token`
+    pub(crate) fn eat(&mut self, token: &str) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// The next token, or an error saying that `what` was expected
+    pub(crate) fn token(&mut self, what: &str) -> ParseResult<&'a str> {
+        let token = self.src.expect(self.peek(), self.line, what)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    pub(crate) fn expect(&mut self, expected: &str) -> ParseResult<()> {
+        let token = self.token(&format!("`{}`", expected))?;
+        if token != expected {
+            return Err(self.src.error(token, format!("expected `{}`", expected)));
+        }
+        Ok(())
+    }
+
+    /// The arguments of a call, up to the next `,` or `)`
+    pub(crate) fn args(&mut self) -> Vec<&'a str> {
+        let mut args = vec![];
+        while let Some(token) = self.peek().filter(|&t| t != "," && t != ")") {
+            args.push(token);
+            self.pos += 1;
+        }
+        args
+    }
+
+    /// Parses `(<item>, <item>, ...)` with `item`
+    pub(crate) fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<Vec<T>> {
+        self.expect("(")?;
+        let mut items = vec![item(self)?];
+        while self.eat(",") {
+            items.push(item(self)?);
+        }
+        self.expect(")")?;
+        Ok(items)
+    }
+
+    /// Fails if there are tokens left
+    pub(crate) fn finish(&self, what: &str) -> ParseResult<()> {
+        match self.peek() {
+            Some(token) => Err(self.error(token, format!("expected {}", what))),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn error(&self, fragment: &str, reason: impl Into<String>) -> ParseError {
+        self.src.error(fragment, reason)
+    }
+}